@@ -0,0 +1,91 @@
+#!/usr/bin/env bash
+# Compares the compile time of the expansion of `query_value!` with the former expansion based on a chain of `Option::and_then` closures.
+#
+# Generates two crates under `target/compile-time-bench`, each with N functions running a query of 8 segments
+# (one with `query_value!`, the other with the former expansion), and reports the median of the times taken to rebuild each of them.
+#
+# usage: benches/compile_time.sh [N (default: 300)] [RUNS (default: 3)] [extra args to `cargo build`, e.g. --release]
+set -euo pipefail
+
+n=${1:-300}
+runs=${2:-3}
+shift $(( $# < 2 ? $# : 2 ))
+
+root=$(cd "$(dirname "$0")/.." && pwd)
+work="$root/target/compile-time-bench"
+export CARGO_TARGET_DIR="$work/target"
+
+gen() {
+    local variant=$1 mac=$2
+    mkdir -p "$work/$variant/src"
+    cat > "$work/$variant/Cargo.toml" <<EOF
+[package]
+name = "compile-time-$variant"
+version = "0.0.0"
+edition = "2021"
+publish = false
+
+[dependencies]
+serde_json = "1.0.120"
+valq = { path = "$root" }
+
+[workspace]
+EOF
+    {
+        cat <<'EOF'
+#![allow(unused_imports, unused_macros)]
+use serde_json::Value;
+use valq::query_value;
+
+/// The former expansion of `query_value!`, as the baseline.
+macro_rules! query_and_then {
+    (@trv { $vopt:expr }) => { $vopt };
+    (@trv { $vopt:expr } -> u64) => { $vopt.and_then(|v| v.as_u64()) };
+    (@trv { $vopt:expr } . $key:ident $($rest:tt)*) => {
+        query_and_then!(@trv { $vopt.and_then(|v| v.get(stringify!($key))) } $($rest)*)
+    };
+    (@trv { $vopt:expr } [ $idx:expr ] $($rest:tt)*) => {
+        query_and_then!(@trv { $vopt.and_then(|v| v.get($idx as usize)) } $($rest)*)
+    };
+    ($v:tt . $key:ident $($rest:tt)*) => {
+        query_and_then!(@trv { $v.get(stringify!($key)) } $($rest)*)
+    };
+}
+EOF
+        for i in $(seq 0 $((n - 1))); do
+            echo "pub fn f$i(v: &Value) -> Option<u64> { $mac!(v.a$i.b.c.d[$((i % 5))].e.f.g -> u64) }"
+        done
+        echo "fn main() {"
+        echo "    let v = Value::Null;"
+        for i in $(seq 0 $((n - 1))); do
+            echo "    println!(\"{:?}\", f$i(&v));"
+        done
+        echo "}"
+    } > "$work/$variant/src/main.rs"
+}
+
+measure() {
+    local variant=$1
+    shift
+    # build once so that only the generated crate is rebuilt in the measurements
+    cargo build -q --manifest-path "$work/$variant/Cargo.toml" "$@"
+    local times=()
+    for _ in $(seq "$runs"); do
+        touch "$work/$variant/src/main.rs"
+        local start end
+        start=$(date +%s%N)
+        cargo build -q --manifest-path "$work/$variant/Cargo.toml" "$@"
+        end=$(date +%s%N)
+        times+=($(( (end - start) / 1000000 )))
+    done
+    local median
+    median=$(printf '%s\n' "${times[@]}" | sort -n | sed -n "$(( (runs + 1) / 2 ))p")
+    printf '%-16s %6d ms (runs: %s)\n' "$variant" "$median" "${times[*]}"
+}
+
+gen labeled-block query_value
+gen and-then-chain query_and_then
+
+echo "$n queries, median of $runs rebuilds${*:+ (cargo build $*)}"
+measure labeled-block "$@"
+measure and-then-chain "$@"
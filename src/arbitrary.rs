@@ -0,0 +1,133 @@
+/// A macro for making a [proptest](https://docs.rs/proptest) strategy that generates values satisfying the given queries, for property-based testing of code built on queries.
+///
+/// Takes the same arguments as [`fixture_value`](crate::fixture_value), but the values at the ends of paths are random values of the conversion types (`str`, `u64`, `i64`/`integer`, `f64`/`float` and `bool`) rather than fixed samples.
+/// Paths with other conversion types or without conversion end with empty arrays/objects as `fixture_value` does.
+///
+/// Available with `proptest` feature, for data types that implement [`Editable`](crate::Editable), `Clone`, `Debug` and `From` for the random values of the conversions used.
+///
+/// By default, generated values have nothing but the required paths.
+/// To check that code doesn't rely on that, write `, extra` after the type: random entries (with random keys and random booleans, integers or strings) are merged into objects, and random elements are appended to arrays, along the required paths.
+/// Entries never replace the required ones and elements are only appended, so the values at the required paths are kept.
+/// This needs `From<bool>`, `From<i64>` and `From<String>` as well.
+///
+/// ```
+/// use proptest::prelude::*;
+/// use serde_json::Value;
+/// use valq::{arb_value, query_value};
+///
+/// proptest!(|(req in arb_value!(Value, extra; .user.name -> str, .tags[0] -> bool))| {
+///     prop_assert!(query_value!(req.user.name -> str).is_some());
+///     prop_assert!(query_value!(req.tags[0] -> bool).is_some());
+/// });
+/// ```
+///
+/// ```
+/// use proptest::prelude::*;
+/// use serde_json::Value;
+/// use valq::{arb_value, query_value};
+///
+/// proptest!(|(req in arb_value!(Value; .user.name -> str, .user.age -> u64))| {
+///     prop_assert!(query_value!(req.user.name -> str).is_some());
+///     prop_assert!(query_value!(req.user.age -> u64).is_some());
+/// });
+/// ```
+#[macro_export]
+macro_rules! arb_value {
+    /* strategies for the values at the ends of paths */
+    (@leaf str) => {
+        $crate::arb_value!(@leaf_of $crate::__private::proptest::arbitrary::any::<String>())
+    };
+    (@leaf u64) => {
+        $crate::arb_value!(@leaf_of $crate::__private::proptest::arbitrary::any::<u64>())
+    };
+    (@leaf i64) => {
+        $crate::arb_value!(@leaf_of $crate::__private::proptest::arbitrary::any::<i64>())
+    };
+    (@leaf integer) => {
+        $crate::arb_value!(@leaf i64)
+    };
+    (@leaf f64) => {
+        // NaN and infinities are not representable in some formats (e.g. JSON)
+        $crate::arb_value!(@leaf_of $crate::__private::proptest::num::f64::NORMAL | $crate::__private::proptest::num::f64::ZERO)
+    };
+    (@leaf float) => {
+        $crate::arb_value!(@leaf f64)
+    };
+    (@leaf bool) => {
+        $crate::arb_value!(@leaf_of $crate::__private::proptest::arbitrary::any::<bool>())
+    };
+    (@leaf $to:ident) => {
+        $crate::__private::proptest::strategy::Strategy::boxed(
+            $crate::__private::proptest::strategy::Just($crate::fixture_value!(@sample $to)),
+        )
+    };
+    (@leaf) => {
+        $crate::__private::proptest::strategy::Strategy::boxed(
+            $crate::__private::proptest::strategy::Just($crate::Editable::new_object()),
+        )
+    };
+    (@leaf_of $s:expr) => {
+        $crate::__private::proptest::strategy::Strategy::boxed(
+            $crate::__private::proptest::strategy::Strategy::prop_map($s, ::core::convert::From::from),
+        )
+    };
+
+    /* collecting strategies for each path */
+    (@leaves $leaves:ident []) => {};
+    (@leaves $leaves:ident [$($path:tt)+] -> $to:ident $(, $($rest:tt)*)?) => {
+        $leaves.push($crate::arb_value!(@leaf $to));
+        $crate::arb_value!(@leaves $leaves [] $($($rest)*)?);
+    };
+    (@leaves $leaves:ident [$($path:tt)+] $(, $($rest:tt)*)?) => {
+        $leaves.push($crate::arb_value!(@leaf));
+        $crate::arb_value!(@leaves $leaves [] $($($rest)*)?);
+    };
+    (@leaves $leaves:ident [$($path:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::arb_value!(@leaves $leaves [$($path)* $t] $($rest)*)
+    };
+
+    /* building a value from generated leaves */
+    (@build $root:ident $leaves:ident []) => {};
+    (@build $root:ident $leaves:ident [$($path:tt)+] $(-> $to:ident)? $(, $($rest:tt)*)?) => {
+        $crate::fixture_value!(@add $root [$($path)+] $leaves.next().unwrap());
+        $crate::arb_value!(@build $root $leaves [] $($($rest)*)?);
+    };
+    (@build $root:ident $leaves:ident [$($path:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::arb_value!(@build $root $leaves [$($path)* $t] $($rest)*)
+    };
+
+    /* entry point */
+    ($t:ty, extra; $($paths:tt)+) => {
+        $crate::__private::proptest::strategy::Strategy::prop_map(
+            (
+                $crate::arb_value!($t; $($paths)+),
+                $crate::__private::proptest::collection::vec(
+                    (
+                        $crate::__private::proptest::arbitrary::any::<usize>(),
+                        $crate::__private::proptest::arbitrary::any::<String>(),
+                        $crate::__private::proptest::prop_oneof![
+                            $crate::arb_value!(@leaf bool),
+                            $crate::arb_value!(@leaf i64),
+                            $crate::arb_value!(@leaf str),
+                        ],
+                    ),
+                    0..8,
+                ),
+            ),
+            |(mut root, extras): ($t, _)| {
+                $crate::__private::merge_extras(&mut root, extras);
+                root
+            },
+        )
+    };
+    ($t:ty; $($paths:tt)+) => {{
+        let mut leaves: Vec<$crate::__private::proptest::strategy::BoxedStrategy<$t>> = Vec::new();
+        $crate::arb_value!(@leaves leaves [] $($paths)+);
+        $crate::__private::proptest::strategy::Strategy::prop_map(leaves, |leaves| {
+            let mut leaves = leaves.into_iter();
+            let mut root: $t = $crate::Editable::new_object();
+            $crate::arb_value!(@build root leaves [] $($paths)+);
+            root
+        })
+    }};
+}
@@ -0,0 +1,311 @@
+use crate::Editable;
+
+/// Compares two values structurally, treating arrays as multisets (i.e. ignoring order of elements).
+/// Objects are compared by their entries, so key order doesn't matter either.
+///
+/// Values at paths listed in `ignore` are excluded from the comparison. Paths are written like `.meta.updated_at`, with `[*]` for elements of arrays (e.g. `.items[*].id`).
+/// Entries with non-string keys (possible in YAML) are not compared. See also [`value_eq_unordered`](crate::value_eq_unordered).
+///
+/// ```
+/// use serde_json::json;
+/// use valq::eq_unordered;
+///
+/// let a = json!({"tags": ["a", "b", "a"], "meta": {"updated_at": 1}});
+/// let b = json!({"meta": {"updated_at": 2}, "tags": ["b", "a", "a"]});
+///
+/// assert!(!eq_unordered(&a, &b, &[]));
+/// assert!(eq_unordered(&a, &b, &[".meta.updated_at"]));
+/// ```
+pub fn eq_unordered<V: Editable + PartialEq>(a: &V, b: &V, ignore: &[&str]) -> bool {
+    let mut path = String::new();
+    eq_at(a, b, ignore, &mut path)
+}
+
+fn eq_at<V: Editable + PartialEq>(a: &V, b: &V, ignore: &[&str], path: &mut String) -> bool {
+    match ((a.entries(), b.entries()), (a.elements(), b.elements())) {
+        ((Some(ea), Some(eb)), _) => {
+            let ea = without_ignored(ea, ignore, path);
+            let eb = without_ignored(eb, ignore, path);
+            ea.len() == eb.len()
+                && ea.iter().all(|(k, va)| {
+                    eb.iter().find(|(kb, _)| kb == k).is_some_and(|(_, vb)| {
+                        let len = path.len();
+                        path.push('.');
+                        path.push_str(k);
+                        let eq = eq_at(*va, *vb, ignore, path);
+                        path.truncate(len);
+                        eq
+                    })
+                })
+        }
+        (_, (Some(ea), Some(eb))) => {
+            if ea.len() != eb.len() {
+                return false;
+            }
+            let len = path.len();
+            path.push_str("[*]");
+            let mut matched = vec![false; eb.len()];
+            let eq = ea.iter().all(|va| {
+                let found = (0..eb.len()).find(|&i| !matched[i] && eq_at(va, &eb[i], ignore, path));
+                if let Some(i) = found {
+                    matched[i] = true;
+                }
+                found.is_some()
+            });
+            path.truncate(len);
+            eq
+        }
+        _ => a == b,
+    }
+}
+
+fn without_ignored<'a, V>(
+    entries: Vec<(&'a str, &'a V)>,
+    ignore: &[&str],
+    path: &str,
+) -> Vec<(&'a str, &'a V)> {
+    if ignore.is_empty() {
+        return entries;
+    }
+    entries
+        .into_iter()
+        .filter(|(k, _)| {
+            !ignore
+                .iter()
+                .any(|p| p.strip_prefix(path).and_then(|p| p.strip_prefix('.')) == Some(k))
+        })
+        .collect()
+}
+
+/// A macro for comparing values at two queries structurally, ignoring order of array elements and object keys.
+///
+/// Takes two queries in the same syntax as [`query_value`](crate::query_value) (except for `mut` and `-> <to_type>`), optionally followed by `, ignore = [<path>, ...]` to exclude values at the paths (relative to the queried values) from the comparison.
+/// See [`eq_unordered`](crate::eq_unordered) for details of the comparison and paths.
+/// Results in `false` if either query fails.
+///
+/// Available for data types that implement [`Editable`](crate::Editable) and `PartialEq`.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::value_eq_unordered;
+///
+/// let expected = json!({"items": [{"id": 1, "name": "a"}, {"id": 2, "name": "b"}]});
+/// let actual = json!({"items": [{"name": "b", "id": 20}, {"name": "a", "id": 10}]});
+///
+/// assert!(!value_eq_unordered!(expected.items, actual.items));
+/// assert!(value_eq_unordered!(expected.items, actual.items, ignore = ["[*].id"]));
+/// ```
+#[macro_export]
+macro_rules! value_eq_unordered {
+    (@cmp [$($a:tt)+] [$($b:tt)+] [$($ignore:expr),*]) => {
+        match ($crate::query_value!($($a)+), $crate::query_value!($($b)+)) {
+            (Some(a), Some(b)) => $crate::eq_unordered(a, b, &[$($ignore),*]),
+            _ => false,
+        }
+    };
+    (@split [$($a:tt)+] [$($b:tt)+] , ignore = [$($ignore:expr),* $(,)?] $(,)?) => {
+        $crate::value_eq_unordered!(@cmp [$($a)+] [$($b)+] [$($ignore),*])
+    };
+    (@split [$($a:tt)+] [$($b:tt)+] $(,)?) => {
+        $crate::value_eq_unordered!(@cmp [$($a)+] [$($b)+] [])
+    };
+    (@split [$($a:tt)+] [$($b:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::value_eq_unordered!(@split [$($a)+] [$($b)* $t] $($rest)*)
+    };
+    (@split_a [$($a:tt)+] , $($rest:tt)+) => {
+        $crate::value_eq_unordered!(@split [$($a)+] [] $($rest)+)
+    };
+    (@split_a [$($a:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::value_eq_unordered!(@split_a [$($a)* $t] $($rest)*)
+    };
+    (@split_a $($_:tt)*) => {
+        compile_error!("invalid syntax for value_eq_unordered!(): expected `value_eq_unordered!(<query>, <query>)`")
+    };
+
+    /* entry point */
+    ($($args:tt)+) => {
+        $crate::value_eq_unordered!(@split_a [] $($args)+)
+    };
+}
+
+/// A macro for checking whether the value at a query matches a pattern, looking only at the parts mentioned in the pattern.
+///
+/// Takes a query in the same syntax as [`query_value`](crate::query_value) (except for `mut` and `-> <to_type>`; it can be just a value), followed by `,` and a pattern. Results in `bool`.
+///
+/// Patterns are:
+///
+/// - `{ <key>: <pattern>, ... }`: an object that has (at least) all the listed entries, whose values match the patterns. `<key>` is an identifier or a `str` literal.
+/// - `[<pattern>, ...]`: an array whose elements match the patterns one by one. If it ends with `..` (e.g. `[1, ..]`), the array can have more elements.
+/// - `_`: any value.
+/// - any other expression: a value equal to it by `==`, so `Value` must implement `PartialEq` for the type of the expression (as `serde_json::Value` does for `&str`, `u64`, `bool`, ...).
+///
+/// Available for data types that implement [`Editable`](crate::Editable).
+///
+/// ```
+/// use serde_json::json;
+/// use valq::value_matches;
+///
+/// let resp = json!({"user": {"name": "alice", "age": 20, "email": "alice@example.com"}, "tags": ["a", "b"]});
+///
+/// assert!(value_matches!(resp, { user: { name: "alice", age: _ }, tags: [..] }));
+/// assert!(value_matches!(resp.tags, ["a", ..]));
+/// assert!(!value_matches!(resp, { user: { name: "bob" } }));
+/// ```
+#[macro_export]
+macro_rules! value_matches {
+    /* matching a value against a pattern */
+    (@pat $v:ident, _) => {{
+        let _ = $v;
+        true
+    }};
+    (@pat $v:ident, { $($entries:tt)* }) => {
+        $crate::Editable::is_object($v) && $crate::value_matches!(@entries $v, $($entries)*)
+    };
+    (@pat $v:ident, [ $($elems:tt)* ]) => {
+        match $crate::Editable::elements($v) {
+            Some(elems) => $crate::value_matches!(@elems elems, 0, $($elems)*),
+            None => false,
+        }
+    };
+    (@pat $v:ident, $e:expr) => {
+        *$v == $e
+    };
+
+    /* entries of object pattern */
+    (@entries $v:ident, $(,)?) => {
+        true
+    };
+    (@entries $v:ident, $key:ident : $($rest:tt)+) => {
+        $crate::value_matches!(@entry $v, [stringify!($key)] [] $($rest)+)
+    };
+    (@entries $v:ident, $key:literal : $($rest:tt)+) => {
+        $crate::value_matches!(@entry $v, [$key] [] $($rest)+)
+    };
+    (@entry $v:ident, [$key:expr] [$($p:tt)+] , $($rest:tt)*) => {
+        $crate::value_matches!(@entry $v, [$key] [$($p)+]) && $crate::value_matches!(@entries $v, $($rest)*)
+    };
+    (@entry $v:ident, [$key:expr] [$($p:tt)+]) => {
+        match $v.get($key as &str) {
+            Some(c) => $crate::value_matches!(@pat c, $($p)+),
+            None => false,
+        }
+    };
+    (@entry $v:ident, [$key:expr] [$($p:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::value_matches!(@entry $v, [$key] [$($p)* $t] $($rest)*)
+    };
+
+    /* elements of array pattern */
+    (@elems $es:ident, $i:expr, $(,)?) => {
+        $es.len() == $i
+    };
+    (@elems $es:ident, $i:expr, .. $(,)?) => {{
+        let min_len: usize = $i;
+        $es.len() >= min_len
+    }};
+    (@elems $es:ident, $i:expr, $($rest:tt)+) => {
+        $crate::value_matches!(@elem $es, $i, [] $($rest)+)
+    };
+    (@elem $es:ident, $i:expr, [$($p:tt)+] , $($rest:tt)*) => {
+        match $es.get($i) {
+            Some(c) => $crate::value_matches!(@pat c, $($p)+),
+            None => false,
+        } && $crate::value_matches!(@elems $es, $i + 1, $($rest)*)
+    };
+    (@elem $es:ident, $i:expr, [$($p:tt)+]) => {
+        $crate::value_matches!(@elem $es, $i, [$($p)+] ,)
+    };
+    (@elem $es:ident, $i:expr, [$($p:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::value_matches!(@elem $es, $i, [$($p)* $t] $($rest)*)
+    };
+
+    /* entry point */
+    (@root $v:tt) => {{
+        use $crate::__private::Reborrow as _;
+        Some($v.reborrow())
+    }};
+    (@root $($query:tt)+) => {
+        $crate::query_value!($($query)+)
+    };
+    (@split [$($query:tt)+] , $($pat:tt)+) => {
+        match $crate::value_matches!(@root $($query)+) {
+            Some(v) => $crate::value_matches!(@pat v, $($pat)+),
+            None => false,
+        }
+    };
+    (@split [$($query:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::value_matches!(@split [$($query)* $t] $($rest)*)
+    };
+    (@split $($_:tt)*) => {
+        compile_error!("invalid syntax for value_matches!(): expected `value_matches!(<query>, <pattern>)`")
+    };
+    ($($args:tt)+) => {
+        $crate::value_matches!(@split [] $($args)+)
+    };
+}
+
+/// A macro for finding which of the watched paths changed between two versions of a value, e.g. for reloading only affected parts of configuration.
+///
+/// Takes the old and new values separated by `,`, followed by `;` and comma-separated paths in the same syntax as [`query_value`](crate::query_value) (except for `mut` and `-> <to_type>`).
+/// Results in a `Vec` of the changed paths (as written, in the same format as [`query_value_with_path`](crate::query_value_with_path), e.g. `".server.port"`) along with the old and new values at them, in the order of the paths.
+/// A path is considered changed if the values at it are not equal (by `PartialEq`), or it exists in only one of the versions (then the other side is `None`).
+///
+/// ```
+/// use serde_json::json;
+/// use valq::watch_paths;
+///
+/// let old = json!({"server": {"port": 80}, "limits": {"rps": 100}, "motd": "hi"});
+/// let new = json!({"server": {"port": 8080}, "limits": {"rps": 100}, "motd": "hello"});
+///
+/// let changes = watch_paths!(old, new; .server, .limits);
+/// assert_eq!(changes, vec![(".server", Some(&old["server"]), Some(&new["server"]))]);
+/// ```
+#[macro_export]
+macro_rules! watch_paths {
+    /* splitting paths */
+    (@split [$old:ident $new:ident] [$([$($done:tt)+])*] [$($cur:tt)+] , $($rest:tt)*) => {
+        $crate::watch_paths! { @split [$old $new] [$([$($done)+])* [$($cur)+]] [] $($rest)* }
+    };
+    (@split [$old:ident $new:ident] [$([$($done:tt)+])*] [$($cur:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::watch_paths! { @split [$old $new] [$([$($done)+])*] [$($cur)* $t] $($rest)* }
+    };
+    (@split [$old:ident $new:ident] [$([$($done:tt)+])*] [$($cur:tt)+]) => {
+        $crate::watch_paths! { @split [$old $new] [$([$($done)+])* [$($cur)+]] [] }
+    };
+    (@split [$old:ident $new:ident] [$([$($path:tt)+])+] []) => {{
+        let mut changes = Vec::new();
+        $(
+            let (o, n) = ($crate::query_value!($old $($path)+), $crate::query_value!($new $($path)+));
+            if o != n {
+                changes.push(($crate::watch_paths!(@path [] $($path)+), o, n));
+            }
+        )+
+        changes
+    }};
+
+    /* rendering paths, in the same format as `query_value_with_path!()` */
+    (@path [$($s:expr),*]) => {
+        concat!($($s),*)
+    };
+    (@path [$($s:expr),*] . $key:tt $($rest:tt)*) => {
+        $crate::watch_paths!(@path [$($s,)* concat!(".", stringify!($key))] $($rest)*)
+    };
+    (@path [$($s:expr),*] .. $key:tt $($rest:tt)*) => {
+        $crate::watch_paths!(@path [$($s,)* concat!("..", stringify!($key))] $($rest)*)
+    };
+    (@path [$($s:expr),*] [- $($n:tt)+] $($rest:tt)*) => {
+        $crate::watch_paths!(@path [$($s,)* concat!("[-", stringify!($($n)+), "]")] $($rest)*)
+    };
+    (@path [$($s:expr),*] [$($idx:tt)+] $($rest:tt)*) => {
+        $crate::watch_paths!(@path [$($s,)* concat!("[", stringify!($($idx)+), "]")] $($rest)*)
+    };
+    (@path [$($s:expr),*] ? $($rest:tt)*) => {
+        $crate::watch_paths!(@path [$($s,)* "?"] $($rest)*)
+    };
+
+    /* entry point */
+    ($old:expr, $new:expr; $($paths:tt)+) => {{
+        let old = &$old;
+        let new = &$new;
+        $crate::watch_paths! { @split [old new] [] [] $($paths)+ }
+    }};
+}
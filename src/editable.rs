@@ -0,0 +1,438 @@
+/// A trait for structured data types whose structure can be edited by valq's mutation macros (e.g. [`compact_value`](crate::compact_value)).
+///
+/// Unlike querying (which works with any type that has `get`/`get_mut` methods), editing structure requires knowledge of how "objects" and "arrays" are represented in the data type, hence this trait.
+/// Searching children of objects and arrays by `[? ...]` segments in [`query_value`](crate::query_value), comparing values by [`eq_unordered`](crate::eq_unordered) and hashing values by [`hash_value`](crate::hash_value) rely on it as well.
+///
+/// Implementations for the following data types are provided, each behind a cargo feature of the same name as the crate:
+///
+/// - `serde_json::Value` (feature: `serde_json`)
+/// - `serde_yaml::Value` (feature: `serde_yaml`)
+/// - `toml::Value` (feature: `toml`)
+pub trait Editable: Sized {
+    /// Creates an empty object.
+    fn new_object() -> Self;
+
+    /// Creates an empty array.
+    fn new_array() -> Self;
+
+    /// Returns `true` if the value is null.
+    fn is_null(&self) -> bool;
+
+    /// Returns `true` if the value is an object.
+    fn is_object(&self) -> bool;
+
+    /// Returns `true` if the value is an array.
+    fn is_array(&self) -> bool;
+
+    /// Returns `true` if the value is an object or an array that has no entries.
+    fn is_empty_container(&self) -> bool;
+
+    /// Retains only the children (values of entries of an object, or elements of an array) for which `f` returns `true`.
+    /// Does nothing if the value is neither an object nor an array.
+    fn retain_children<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut Self) -> bool;
+
+    /// Returns the elements if the value is an array.
+    fn elements(&self) -> Option<&[Self]>;
+
+    /// Returns the entries (pairs of key and value) if the value is an object. Entries with non-string keys are skipped.
+    fn entries(&self) -> Option<Vec<(&str, &Self)>>;
+
+    /// Returns the first entry of the value (if it is an object) whose value satisfies `f`, as a pair of the key and the value.
+    /// Entries with non-string keys are skipped.
+    fn find_entry<F>(&self, f: F) -> Option<(&str, &Self)>
+    where
+        F: FnMut(&Self) -> bool;
+
+    /// Returns the mutable reference to the elements if the value is an array.
+    fn elements_mut(&mut self) -> Option<&mut Vec<Self>>;
+
+    /// Calls `f` with the mutable reference to each child (the value of an entry of an object, or an element of an array), in order.
+    /// Does nothing if the value is neither an object nor an array.
+    fn for_each_child_mut<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut Self);
+
+    /// Inserts an entry into the value if it is an object, and returns the value previously associated with the key.
+    /// Does nothing (and returns `None`) if the value is not an object.
+    fn insert_entry(&mut self, key: &str, value: Self) -> Option<Self>;
+
+    /// Removes an entry from the value if it is an object, and returns the value that was associated with the key.
+    /// Does nothing (and returns `None`) if the value is not an object or has no such entry.
+    fn remove_entry(&mut self, key: &str) -> Option<Self>;
+
+    /// Writes the canonical textual form of the value to `out`, if it is neither an object nor an array.
+    /// Integers are written in decimal, and floats always with a fractional part or an exponent (e.g. `1.0`, `1e20`), so that an integer and a float never have the same form (as they compare unequal).
+    /// Strings are written with a leading `"` (without escaping).
+    /// Used by [`hash_value`](crate::hash_value) to hash leaves of values.
+    fn write_scalar(&self, out: &mut String);
+
+    /// Returns the name of the kind of the value: one of `"null"`, `"bool"`, `"number"`, `"string"`, `"array"` and `"object"`, or a name of a kind specific to the data type (e.g. `"datetime"` of TOML).
+    /// Used by [`explain_query`](crate::explain_query) to report what was found at each step.
+    fn kind(&self) -> &'static str;
+}
+
+#[cfg(any(feature = "serde_json", feature = "serde_yaml", feature = "toml"))]
+fn write_display<D: std::fmt::Display>(out: &mut String, d: D) {
+    use std::fmt::Write as _;
+    let _ = write!(out, "{}", d);
+}
+
+// `Debug` of floats always has a fractional part or an exponent, which tells floats apart from integers
+#[cfg(any(feature = "serde_json", feature = "serde_yaml", feature = "toml"))]
+fn write_float(out: &mut String, f: f64) {
+    use std::fmt::Write as _;
+    // `-0.0` is equal to `0.0`
+    let f = if f == 0.0 { 0.0 } else { f };
+    let _ = write!(out, "{:?}", f);
+}
+
+#[cfg(feature = "serde_json")]
+impl Editable for serde_json::Value {
+    fn new_object() -> Self {
+        serde_json::Value::Object(serde_json::Map::new())
+    }
+
+    fn new_array() -> Self {
+        serde_json::Value::Array(Vec::new())
+    }
+
+    fn is_null(&self) -> bool {
+        self.is_null()
+    }
+
+    fn is_object(&self) -> bool {
+        self.is_object()
+    }
+
+    fn is_array(&self) -> bool {
+        self.is_array()
+    }
+
+    fn is_empty_container(&self) -> bool {
+        match self {
+            serde_json::Value::Object(m) => m.is_empty(),
+            serde_json::Value::Array(a) => a.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn retain_children<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Self) -> bool,
+    {
+        match self {
+            serde_json::Value::Object(m) => m.retain(|_, v| f(v)),
+            serde_json::Value::Array(a) => a.retain_mut(f),
+            _ => {}
+        }
+    }
+
+    fn elements(&self) -> Option<&[Self]> {
+        self.as_array().map(Vec::as_slice)
+    }
+
+    fn entries(&self) -> Option<Vec<(&str, &Self)>> {
+        self.as_object()
+            .map(|m| m.iter().map(|(k, v)| (k.as_str(), v)).collect())
+    }
+
+    fn find_entry<F>(&self, mut f: F) -> Option<(&str, &Self)>
+    where
+        F: FnMut(&Self) -> bool,
+    {
+        self.as_object()?
+            .iter()
+            .find(|(_, v)| f(v))
+            .map(|(k, v)| (k.as_str(), v))
+    }
+
+    fn elements_mut(&mut self) -> Option<&mut Vec<Self>> {
+        self.as_array_mut()
+    }
+
+    fn for_each_child_mut<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut Self),
+    {
+        match self {
+            serde_json::Value::Object(m) => m.values_mut().for_each(f),
+            serde_json::Value::Array(a) => a.iter_mut().for_each(f),
+            _ => {}
+        }
+    }
+
+    fn insert_entry(&mut self, key: &str, value: Self) -> Option<Self> {
+        self.as_object_mut()
+            .and_then(|m| m.insert(key.to_string(), value))
+    }
+
+    fn remove_entry(&mut self, key: &str) -> Option<Self> {
+        self.as_object_mut().and_then(|m| m.remove(key))
+    }
+
+    fn write_scalar(&self, out: &mut String) {
+        match self {
+            serde_json::Value::Null => out.push_str("null"),
+            serde_json::Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            serde_json::Value::Number(n) => match (n.as_u64(), n.as_i64(), n.as_f64()) {
+                (Some(u), _, _) => write_display(out, u),
+                (_, Some(i), _) => write_display(out, i),
+                (_, _, Some(f)) => write_float(out, f),
+                _ => write_display(out, n),
+            },
+            serde_json::Value::String(s) => {
+                out.push('"');
+                out.push_str(s);
+            }
+            serde_json::Value::Array(_) | serde_json::Value::Object(_) => {}
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            serde_json::Value::Null => "null",
+            serde_json::Value::Bool(_) => "bool",
+            serde_json::Value::Number(_) => "number",
+            serde_json::Value::String(_) => "string",
+            serde_json::Value::Array(_) => "array",
+            serde_json::Value::Object(_) => "object",
+        }
+    }
+}
+
+#[cfg(feature = "serde_yaml")]
+impl Editable for serde_yaml::Value {
+    fn new_object() -> Self {
+        serde_yaml::Value::Mapping(serde_yaml::Mapping::new())
+    }
+
+    fn new_array() -> Self {
+        serde_yaml::Value::Sequence(Vec::new())
+    }
+
+    fn is_null(&self) -> bool {
+        self.is_null()
+    }
+
+    fn is_object(&self) -> bool {
+        self.is_mapping()
+    }
+
+    fn is_array(&self) -> bool {
+        self.is_sequence()
+    }
+
+    fn is_empty_container(&self) -> bool {
+        match self {
+            serde_yaml::Value::Mapping(m) => m.is_empty(),
+            serde_yaml::Value::Sequence(s) => s.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn retain_children<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Self) -> bool,
+    {
+        match self {
+            serde_yaml::Value::Mapping(m) => m.retain(|_, v| f(v)),
+            serde_yaml::Value::Sequence(s) => s.retain_mut(f),
+            _ => {}
+        }
+    }
+
+    fn elements(&self) -> Option<&[Self]> {
+        self.as_sequence().map(Vec::as_slice)
+    }
+
+    fn entries(&self) -> Option<Vec<(&str, &Self)>> {
+        self.as_mapping().map(|m| {
+            m.iter()
+                .filter_map(|(k, v)| k.as_str().map(|k| (k, v)))
+                .collect()
+        })
+    }
+
+    fn find_entry<F>(&self, mut f: F) -> Option<(&str, &Self)>
+    where
+        F: FnMut(&Self) -> bool,
+    {
+        self.as_mapping()?
+            .iter()
+            .find_map(|(k, v)| k.as_str().filter(|_| f(v)).map(|k| (k, v)))
+    }
+
+    fn elements_mut(&mut self) -> Option<&mut Vec<Self>> {
+        self.as_sequence_mut()
+    }
+
+    fn for_each_child_mut<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut Self),
+    {
+        match self {
+            serde_yaml::Value::Mapping(m) => m.values_mut().for_each(f),
+            serde_yaml::Value::Sequence(s) => s.iter_mut().for_each(f),
+            _ => {}
+        }
+    }
+
+    fn insert_entry(&mut self, key: &str, value: Self) -> Option<Self> {
+        self.as_mapping_mut()
+            .and_then(|m| m.insert(serde_yaml::Value::String(key.to_string()), value))
+    }
+
+    fn remove_entry(&mut self, key: &str) -> Option<Self> {
+        self.as_mapping_mut().and_then(|m| m.remove(key))
+    }
+
+    // tagged scalars are written as the tag followed by the inner value
+    fn write_scalar(&self, out: &mut String) {
+        match self {
+            serde_yaml::Value::Null => out.push_str("null"),
+            serde_yaml::Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            serde_yaml::Value::Number(n) => match (n.as_u64(), n.as_i64(), n.as_f64()) {
+                (Some(u), _, _) => write_display(out, u),
+                (_, Some(i), _) => write_display(out, i),
+                (_, _, Some(f)) => write_float(out, f),
+                _ => write_display(out, n),
+            },
+            serde_yaml::Value::String(s) => {
+                out.push('"');
+                out.push_str(s);
+            }
+            serde_yaml::Value::Tagged(t) => {
+                write_display(out, &t.tag);
+                out.push(' ');
+                t.value.write_scalar(out);
+            }
+            serde_yaml::Value::Sequence(_) | serde_yaml::Value::Mapping(_) => {}
+        }
+    }
+
+    // tagged values are of the kind of the inner value
+    fn kind(&self) -> &'static str {
+        match self {
+            serde_yaml::Value::Null => "null",
+            serde_yaml::Value::Bool(_) => "bool",
+            serde_yaml::Value::Number(_) => "number",
+            serde_yaml::Value::String(_) => "string",
+            serde_yaml::Value::Sequence(_) => "array",
+            serde_yaml::Value::Mapping(_) => "object",
+            serde_yaml::Value::Tagged(t) => t.value.kind(),
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl Editable for toml::Value {
+    fn new_object() -> Self {
+        toml::Value::Table(toml::Table::new())
+    }
+
+    fn new_array() -> Self {
+        toml::Value::Array(Vec::new())
+    }
+
+    // TOML has no null
+    fn is_null(&self) -> bool {
+        false
+    }
+
+    fn is_object(&self) -> bool {
+        self.is_table()
+    }
+
+    fn is_array(&self) -> bool {
+        self.is_array()
+    }
+
+    fn is_empty_container(&self) -> bool {
+        match self {
+            toml::Value::Table(t) => t.is_empty(),
+            toml::Value::Array(a) => a.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn retain_children<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Self) -> bool,
+    {
+        match self {
+            toml::Value::Table(t) => t.retain(|_, v| f(v)),
+            toml::Value::Array(a) => a.retain_mut(f),
+            _ => {}
+        }
+    }
+
+    fn elements(&self) -> Option<&[Self]> {
+        self.as_array().map(Vec::as_slice)
+    }
+
+    fn entries(&self) -> Option<Vec<(&str, &Self)>> {
+        self.as_table()
+            .map(|t| t.iter().map(|(k, v)| (k.as_str(), v)).collect())
+    }
+
+    fn find_entry<F>(&self, mut f: F) -> Option<(&str, &Self)>
+    where
+        F: FnMut(&Self) -> bool,
+    {
+        self.as_table()?
+            .iter()
+            .find(|(_, v)| f(v))
+            .map(|(k, v)| (k.as_str(), v))
+    }
+
+    fn elements_mut(&mut self) -> Option<&mut Vec<Self>> {
+        self.as_array_mut()
+    }
+
+    fn for_each_child_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Self),
+    {
+        match self {
+            toml::Value::Table(t) => t.iter_mut().for_each(|(_, v)| f(v)),
+            toml::Value::Array(a) => a.iter_mut().for_each(f),
+            _ => {}
+        }
+    }
+
+    fn insert_entry(&mut self, key: &str, value: Self) -> Option<Self> {
+        self.as_table_mut()
+            .and_then(|t| t.insert(key.to_string(), value))
+    }
+
+    fn remove_entry(&mut self, key: &str) -> Option<Self> {
+        self.as_table_mut().and_then(|t| t.remove(key))
+    }
+
+    fn write_scalar(&self, out: &mut String) {
+        match self {
+            toml::Value::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+            toml::Value::Integer(i) => write_display(out, i),
+            toml::Value::Float(f) => write_float(out, *f),
+            toml::Value::String(s) => {
+                out.push('"');
+                out.push_str(s);
+            }
+            toml::Value::Datetime(d) => write_display(out, d),
+            toml::Value::Array(_) | toml::Value::Table(_) => {}
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            toml::Value::Boolean(_) => "bool",
+            toml::Value::Integer(_) | toml::Value::Float(_) => "number",
+            toml::Value::String(_) => "string",
+            toml::Value::Datetime(_) => "datetime",
+            toml::Value::Array(_) => "array",
+            toml::Value::Table(_) => "object",
+        }
+    }
+}
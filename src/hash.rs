@@ -0,0 +1,86 @@
+use crate::Editable;
+
+/// Computes a content hash of a value, for change detection or cache keys.
+///
+/// The hash is computed over a canonical form of the value (see [`Editable::write_scalar`]), so it doesn't depend on order of object keys or the type an integer is stored as.
+/// Integers and floats are told apart (e.g. `1` and `1.0` hash differently), as they compare unequal.
+/// The hash function (64-bit FNV-1a) and the canonical form are fixed, so hashes are stable across runs, platforms and releases of valq (known hashes are pinned in tests).
+/// Entries with non-string keys (possible in YAML) are not hashed. Note that this is not a cryptographic hash.
+///
+/// Combine with [`query_value`](crate::query_value) to hash a subtree at a path:
+///
+/// ```
+/// use serde_json::json;
+/// use valq::{hash_value, query_value};
+///
+/// let before = json!({"config": {"port": 8080, "host": "localhost"}, "updated_at": 1});
+/// let after = json!({"updated_at": 2, "config": {"host": "localhost", "port": 8080}});
+///
+/// assert_eq!(
+///     query_value!(before.config).map(hash_value),
+///     query_value!(after.config).map(hash_value),
+/// );
+/// assert_ne!(hash_value(&before), hash_value(&after));
+/// ```
+pub fn hash_value<V: Editable>(v: &V) -> u64 {
+    let mut hasher = Fnv1a::new();
+    let mut buf = String::new();
+    // traverses with an explicit stack rather than recursion, so that deeply nested values don't overflow the stack.
+    // children are pushed in reverse, so that they are hashed in order
+    let mut stack = vec![Item::Value(v)];
+    while let Some(item) = stack.pop() {
+        let v = match item {
+            Item::Key(k) => {
+                hasher.write_len(k.len());
+                hasher.write(k.as_bytes());
+                continue;
+            }
+            Item::Value(v) => v,
+        };
+        if let Some(mut entries) = v.entries() {
+            entries.sort_unstable_by_key(|(k, _)| *k);
+            hasher.write(b"{");
+            hasher.write_len(entries.len());
+            for (k, v) in entries.into_iter().rev() {
+                stack.push(Item::Value(v));
+                stack.push(Item::Key(k));
+            }
+        } else if let Some(elems) = v.elements() {
+            hasher.write(b"[");
+            hasher.write_len(elems.len());
+            stack.extend(elems.iter().rev().map(Item::Value));
+        } else {
+            buf.clear();
+            v.write_scalar(&mut buf);
+            hasher.write(b"=");
+            hasher.write_len(buf.len());
+            hasher.write(buf.as_bytes());
+        }
+    }
+    hasher.0
+}
+
+enum Item<'a, V> {
+    Key(&'a str),
+    Value(&'a V),
+}
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    // lengths are written in fixed width, so that the hash doesn't depend on the platform
+    fn write_len(&mut self, len: usize) {
+        self.write(&(len as u64).to_le_bytes());
+    }
+}
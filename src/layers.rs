@@ -0,0 +1,139 @@
+/// An ordered stack of named documents, where layers added later take precedence over earlier ones.
+///
+/// Typical usage is layering configuration sources: defaults first, then a config file, then overrides from environment.
+/// Use [`query_layers`](crate::query_layers) to look up a value from the layer with the highest precedence that has it.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::{query_layers, Layers};
+///
+/// let layers = Layers::new()
+///     .with_layer("defaults", json!({"server": {"host": "localhost", "port": 8080}}))
+///     .with_layer("env", json!({"server": {"port": 3000}}));
+///
+/// assert_eq!(query_layers!(layers.server.port -> u64), Some(("env", 3000)));
+/// assert_eq!(query_layers!(layers.server.host -> str), Some(("defaults", "localhost")));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layers<V> {
+    layers: Vec<(String, V)>,
+}
+
+impl<V> Layers<V> {
+    /// Creates an empty stack of layers.
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    /// Adds a layer on top of the stack, then returns the stack itself.
+    pub fn with_layer(mut self, name: impl Into<String>, doc: V) -> Self {
+        self.push(name, doc);
+        self
+    }
+
+    /// Adds a layer on top of the stack. The added layer takes precedence over all existing layers.
+    pub fn push(&mut self, name: impl Into<String>, doc: V) {
+        self.layers.push((name.into(), doc));
+    }
+
+    /// Returns the document of the layer named `name`.
+    pub fn layer(&self, name: &str) -> Option<&V> {
+        self.layers
+            .iter()
+            .find_map(|(n, doc)| (n == name).then_some(doc))
+    }
+
+    /// Returns the mutable reference to the document of the layer named `name`.
+    pub fn layer_mut(&mut self, name: &str) -> Option<&mut V> {
+        self.layers
+            .iter_mut()
+            .find_map(|(n, doc)| (n == name).then_some(doc))
+    }
+
+    /// Returns the mutable reference to the document of the layer named `name`, adding a layer with the document made by `f` on top of the stack if there is no such layer.
+    pub fn layer_mut_or_push_with(&mut self, name: &str, f: impl FnOnce() -> V) -> &mut V {
+        let idx = match self.layers.iter().position(|(n, _)| n == name) {
+            Some(idx) => idx,
+            None => {
+                self.push(name, f());
+                self.layers.len() - 1
+            }
+        };
+        &mut self.layers[idx].1
+    }
+
+    /// Iterates over pairs of layer name and document, from the layer with the highest precedence to the lowest.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &V)> {
+        self.layers.iter().rev().map(|(n, doc)| (n.as_str(), doc))
+    }
+
+    /// Applies `f` to each document in order of precedence, and returns the first `Some` result along with the name of the layer it came from.
+    pub fn resolve<'a, T>(&'a self, mut f: impl FnMut(&'a V) -> Option<T>) -> Option<(&'a str, T)> {
+        self.iter().find_map(|(n, doc)| f(doc).map(|v| (n, v)))
+    }
+}
+
+impl<V> Default for Layers<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A macro for querying a value from [`Layers`](crate::Layers) of structured data.
+///
+/// Takes the same query syntax as [`query_value`](crate::query_value) (except for `mut`), and evaluates it against each layer in order of precedence.
+/// The result is a pair of the name of the first layer the query resolved in and the queried value, or `None` if no layer resolved it.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::{query_layers, Layers};
+///
+/// let layers = Layers::new()
+///     .with_layer("defaults", json!({"log": {"level": "info", "color": true}}))
+///     .with_layer("file", json!({"log": {"level": "debug"}}));
+///
+/// assert_eq!(query_layers!(layers.log.level -> str), Some(("file", "debug")));
+/// assert_eq!(query_layers!(layers.log.color -> bool), Some(("defaults", true)));
+/// assert_eq!(query_layers!(layers.log.format), None);
+/// ```
+#[macro_export]
+macro_rules! query_layers {
+    ($layers:tt $($query:tt)+) => {
+        $layers.resolve(|doc| $crate::query_value!(doc $($query)+))
+    };
+}
+
+/// A macro for writing a value into a designated layer of [`Layers`](crate::Layers), leaving the other layers untouched.
+///
+/// Takes the layers and the name of the layer to write into (typically an "override" layer) separated by `,`, followed by `;` and an assignment in the same syntax as [`upsert_value`](crate::upsert_value) without the root value; `mut` before the layers can be omitted.
+/// The value is assigned in the designated layer, creating objects/arrays along the path. If there is no such layer, an empty one is added on top of the stack.
+/// Since lower layers (e.g. defaults) are never mutated, [`query_layers`](crate::query_layers) keeps telling where each value comes from.
+///
+/// Results in the mutable reference to the assigned value, or `None` if the path can't be created in the layer.
+/// Note that the written value is shadowed if a layer with higher precedence than the designated one has a value at the path.
+///
+/// Available for data types that implement [`Editable`](crate::Editable).
+///
+/// ```
+/// use serde_json::json;
+/// use valq::{query_layers, upsert_layers, Layers};
+///
+/// let mut layers = Layers::new()
+///     .with_layer("defaults", json!({"server": {"host": "localhost", "port": 8080}}));
+///
+/// upsert_layers!(mut layers, "overrides"; .server.port = json!(3000));
+///
+/// assert_eq!(query_layers!(layers.server.port -> u64), Some(("overrides", 3000)));
+/// assert_eq!(query_layers!(layers.server.host -> str), Some(("defaults", "localhost")));
+/// assert_eq!(layers.layer("defaults"), Some(&json!({"server": {"host": "localhost", "port": 8080}})));
+/// ```
+#[macro_export]
+macro_rules! upsert_layers {
+    (mut $layers:tt, $name:expr; $($assign:tt)+) => {
+        $crate::upsert_layers!($layers, $name; $($assign)+)
+    };
+    ($layers:tt, $name:expr; $($assign:tt)+) => {{
+        let doc = $layers.layer_mut_or_push_with($name, $crate::Editable::new_object);
+        $crate::upsert_value!(mut doc $($assign)+)
+    }};
+}
@@ -11,7 +11,295 @@
 //! let deep_val: Option<&Value> = query_value!(j.path.to.value.at.deep);
 //! ```
 //!
-//! For now, there is only single macro exported: `query_value`. See document of `query_value` for detailed usage.
+//! The main macro is `query_value`. See document of `query_value` for detailed usage.
+//!
+//! There are also some helpers built on top of it:
+//!
+//! - [`query_all`]: collecting all values matching a query with segments selecting multiple values (`[*]` and `..<key>`).
+//! - [`query_value_owned`]: querying and cloning the result, for when it must outlive the queried data.
+//! - [`query_value_with_path`]: querying along with the path to the result, e.g. for recording what is modified.
+//! - [`query_ancestors`]: collecting every value along the path of a query.
+//! - [`explain_query`]: explaining how a query is resolved step by step, for debugging (needs [`Editable`]).
+//! - [`pointer_of`]: converting a query into a JSON Pointer string at compile time, e.g. for JSON Patch documents.
+//! - [`query_tuple`]: querying multiple values under a single root at once.
+//! - [`try_query`]: querying a mandatory value, returning early from the function if it's missing.
+//! - [`query_value_result`]: querying an optional value, telling a missing (or null) value apart from a value of unexpected type (needs [`Editable`]).
+//! - [`assert_value_approx`]: asserting that a float at a query is approximately equal to the expected one, in tests.
+//! - [`value_eq_unordered`] / [`eq_unordered`]: comparing values ignoring order of array elements and object keys, optionally ignoring some paths (needs [`Editable`], see below).
+//! - [`watch_paths`]: finding which of the watched paths changed between two versions of a value, e.g. on reloading configuration.
+//! - [`hash_value`]: computing a stable content hash of a value (e.g. at a query), for change detection or cache keys (needs [`Editable`]).
+//! - [`value_stats`] / [`stats_at`]: computing shape metrics (depth, number of values, lengths of arrays, ...) of a value, e.g. for guarding against abusive payloads (needs [`Editable`] and [`Queryable`]).
+//! - [`value_matches`]: checking whether a value matches a pattern, looking only at the parts mentioned in it (needs [`Editable`]).
+//! - [`position_of`]: finding the index of the first array element that satisfies a predicate.
+//! - [`query_page`]: taking a page out of an array along with its total length, e.g. for paginated API responses.
+//! - [`for_each_value_mut`] / [`update_where`]: applying a mutation to every value matching a path with wildcards or filters.
+//! - [`Query`]: parsing a query at runtime and evaluating it, for when paths come from config files or user input (needs [`Queryable`]).
+//! - [`Layers`] and [`query_layers`]: querying stacked documents (e.g. defaults, config file, overrides) with precedence.
+//!     + [`upsert_layers`] writes values into a designated layer (e.g. overrides), keeping the other layers intact.
+//!
+//! And some macros for editing structure of values:
+//!
+//! - [`compact_value`]: removing nulls (and optionally, empty objects/arrays) recursively.
+//! - [`ensure_path`]: creating objects/arrays along a path if they don't exist yet.
+//! - [`set_value`]: assigning a value at an existing path, returning the old value.
+//! - [`upsert_value`]: assigning a value at a path, creating objects/arrays along the path if they don't exist yet.
+//! - [`copy_value`]: copying a value from a path to another path.
+//! - [`migrate_value`]: migrating a value by moving values between paths (optionally transforming them), driven by a list of rules.
+//! - [`fixture_value`]: generating a minimal sample value that satisfies given queries, for tests.
+//!     + With `proptest` feature, [`arb_value`] makes a [proptest](https://docs.rs/proptest) strategy generating random values that satisfy given queries.
+//! - [`insert_at`] / [`pop_value`]: inserting a value into an array at a position / removing the last element of an array.
+//! - [`delete_value`]: removing an entry of an object or an element of an array at a path.
+//!
+//! They need the data type to implement [`Editable`], which is implemented for `serde_json::Value`, `serde_yaml::Value` and `toml::Value` behind cargo features of the same names.
+
+#[cfg(feature = "proptest")]
+mod arbitrary;
+mod compare;
+mod editable;
+mod hash;
+mod layers;
+mod mutate;
+mod query;
+mod queryable;
+mod stats;
+mod value_as;
+pub use compare::eq_unordered;
+pub use editable::Editable;
+pub use hash::hash_value;
+pub use layers::Layers;
+pub use mutate::compact;
+pub use query::{Query, QueryParseError};
+pub use queryable::Queryable;
+pub use stats::{value_stats, ValueStats};
+pub use value_as::ValueAs;
+
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "proptest")]
+    pub use proptest;
+
+    /// Turns both `v` and `&mut v` into `&mut v` by autoref of method call, so that macros can take either as a root.
+    pub trait ReborrowMut {
+        fn reborrow_mut(&mut self) -> &mut Self {
+            self
+        }
+    }
+    impl<T: ?Sized> ReborrowMut for T {}
+
+    /// Turns `v`, `&v` and `&mut v` into `&v` by method call, so that macros can pass any of them to [`Editable`](crate::Editable) methods.
+    pub trait Reborrow {
+        fn reborrow(&self) -> &Self {
+            self
+        }
+    }
+    impl<T: crate::Editable> Reborrow for T {}
+
+    /// Collects `v` and all values nested in it, in document order (pre-order), for the recursive descent `..` in queries.
+    pub fn descendants<V: crate::Editable>(v: &V) -> Vec<&V> {
+        let mut acc = Vec::new();
+        let mut stack = vec![v];
+        while let Some(v) = stack.pop() {
+            acc.push(v);
+            if let Some(entries) = v.entries() {
+                stack.extend(entries.into_iter().rev().map(|(_, e)| e));
+            } else if let Some(elems) = v.elements() {
+                stack.extend(elems.iter().rev());
+            }
+        }
+        acc
+    }
+
+    /// Merges extra entries into objects and extra elements into arrays in `v` (including itself), for `arb_value!(<type>, extra; ...)`.
+    /// Each extra goes to the container picked by its index (modulo the number of containers).
+    /// Entries with existing keys are dropped and elements are appended, so that values at the required paths are kept.
+    #[cfg(feature = "proptest")]
+    pub fn merge_extras<V: crate::Editable>(v: &mut V, extras: Vec<(usize, String, V)>) {
+        fn merge<V: crate::Editable>(
+            v: &mut V,
+            buckets: &mut std::vec::IntoIter<Vec<(String, V)>>,
+        ) {
+            if !v.is_object() && !v.is_array() {
+                return;
+            }
+            let extras = buckets.next().unwrap_or_default();
+            // children first, so that the extras are not visited
+            v.for_each_child_mut(|c| merge(c, buckets));
+            for (k, e) in extras {
+                if let Some(elems) = v.elements_mut() {
+                    elems.push(e);
+                } else if v
+                    .entries()
+                    .is_some_and(|es| es.iter().all(|(ek, _)| *ek != k))
+                {
+                    v.insert_entry(&k, e);
+                }
+            }
+        }
+
+        let n = descendants(v)
+            .into_iter()
+            .filter(|d| d.is_object() || d.is_array())
+            .count();
+        if n == 0 {
+            return;
+        }
+        let mut buckets: Vec<Vec<(String, V)>> = std::iter::repeat_with(Vec::new).take(n).collect();
+        for (i, k, e) in extras {
+            buckets[i % n].push((k, e));
+        }
+        merge(v, &mut buckets.into_iter());
+    }
+
+    /// Position of a child in its parent, resolved from a segment selecting a child by its value (e.g. `[? ...]`).
+    pub enum ChildPos {
+        Key(String),
+        Index(usize),
+    }
+
+    impl ChildPos {
+        /// Appends the segment pointing to the child to `path`, in the same format as paths from [`query_value_with_path`](crate::query_value_with_path).
+        pub fn push_to(&self, path: &mut String) {
+            use std::fmt::Write as _;
+            let _ = match self {
+                ChildPos::Key(k) => crate::query::write_key(path, k),
+                ChildPos::Index(i) => write!(path, "[{}]", i),
+            };
+        }
+    }
+
+    /// Finds the first child (the value of an entry of an object, or an element of an array) of `v` that satisfies `pred`, for `[? ...]` segments in queries.
+    pub fn find_child<V: crate::Editable>(
+        v: &V,
+        mut pred: impl FnMut(&V) -> bool,
+    ) -> Option<ChildPos> {
+        if let Some((k, _)) = v.find_entry(&mut pred) {
+            return Some(ChildPos::Key(k.to_owned()));
+        }
+        v.elements()?.iter().position(pred).map(ChildPos::Index)
+    }
+
+    /// Calls `visitor` with `v`. Passing the visitor as an argument lets the types of its parameters be inferred from `v`, for [`for_each_value_mut`](crate::for_each_value_mut).
+    pub fn visit_mut<V: ?Sized, R>(v: &mut V, visitor: impl FnOnce(&mut V) -> R) {
+        visitor(v);
+    }
+
+    /// Resolves the value of a negative index segment (e.g. `[-1]`) into the index from the start of an array of length `len`.
+    /// Negative values count from the end, and non-negative ones (e.g. `[-a + b]` with `a < b`) from the start.
+    pub fn resolve_index(len: usize, idx: i64) -> Option<usize> {
+        if idx < 0 {
+            len.checked_sub(usize::try_from(idx.unsigned_abs()).ok()?)
+        } else {
+            usize::try_from(idx).ok()
+        }
+    }
+
+    /// Describes why a child wasn't found in `parent`, which a segment expected to be of one of the kinds in `expected`, for [`explain_query`](crate::explain_query).
+    pub fn not_found_reason<V: crate::Editable>(parent: &V, expected: &[&str]) -> String {
+        let kind = parent.kind();
+        if !expected.contains(&kind) {
+            return format!("expected {}, found {}", expected.join(" or "), kind);
+        }
+        match parent.elements() {
+            Some(elems) => format!("in array of length {}", elems.len()),
+            None => format!("in {}", kind),
+        }
+    }
+
+    /// Whether the path made of `segs` is the same as the path made of `prefix`, or nested under it.
+    /// Segments are compared as written, for checking paths at compile time.
+    pub const fn path_starts_with(segs: &[&str], prefix: &[&str]) -> bool {
+        if segs.len() < prefix.len() {
+            return false;
+        }
+        let mut i = 0;
+        while i < prefix.len() {
+            let (a, b) = (segs[i].as_bytes(), prefix[i].as_bytes());
+            if a.len() != b.len() {
+                return false;
+            }
+            let mut j = 0;
+            while j < a.len() {
+                if a[j] != b[j] {
+                    return false;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    /// Checks that `idx` (a stringified literal) is an array index that can be put into a JSON Pointer as it is,
+    /// i.e. an unsuffixed non-negative integer literal without `_` or leading zeros.
+    pub const fn pointer_index(idx: &str) -> &str {
+        let b = idx.as_bytes();
+        let mut valid = !b.is_empty() && (b[0] != b'0' || b.len() == 1);
+        let mut i = 0;
+        while i < b.len() {
+            valid &= b[i].is_ascii_digit();
+            i += 1;
+        }
+        if !valid {
+            panic!("index of pointer_of!() must be an unsuffixed non-negative integer literal (e.g. `[0]`)");
+        }
+        idx
+    }
+
+    /// Length of the JSON Pointer made of `segs`, for sizing the buffer of [`json_pointer`].
+    pub const fn json_pointer_len(segs: &[&str]) -> usize {
+        let mut len = 0;
+        let mut i = 0;
+        while i < segs.len() {
+            len += 1;
+            let seg = segs[i].as_bytes();
+            let mut j = 0;
+            while j < seg.len() {
+                len += if seg[j] == b'~' || seg[j] == b'/' {
+                    2
+                } else {
+                    1
+                };
+                j += 1;
+            }
+            i += 1;
+        }
+        len
+    }
+
+    /// Builds the JSON Pointer made of `segs` at compile time, escaping `~` and `/` as `~0` and `~1`.
+    pub const fn json_pointer<const N: usize>(segs: &[&str]) -> [u8; N] {
+        let mut buf = [0u8; N];
+        let mut pos = 0;
+        let mut i = 0;
+        while i < segs.len() {
+            buf[pos] = b'/';
+            pos += 1;
+            let seg = segs[i].as_bytes();
+            let mut j = 0;
+            while j < seg.len() {
+                match seg[j] {
+                    b'~' => {
+                        buf[pos] = b'~';
+                        buf[pos + 1] = b'0';
+                        pos += 2;
+                    }
+                    b'/' => {
+                        buf[pos] = b'~';
+                        buf[pos + 1] = b'1';
+                        pos += 2;
+                    }
+                    b => {
+                        buf[pos] = b;
+                        pos += 1;
+                    }
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        buf
+    }
+}
 
 /// A macro for querying inner value of structured data.
 ///
@@ -34,12 +322,100 @@
 /// let abyss = query_value!(obj.path.to.matrix[0][1].abyss);
 /// ```
 ///
+/// ## Indexing from the End of Array
+/// ```
+/// use serde_json::json;
+/// use valq::query_value;
+///
+/// let obj = json!({"arr": ["first", "second", "last"]});
+///
+/// // negative index counts from the end, like `Array.prototype.at()` in JavaScript
+/// assert_eq!(query_value!(obj.arr[-1] -> str), Some("last"));
+/// assert_eq!(query_value!(obj.arr[-3] -> str), Some("first"));
+/// assert_eq!(query_value!(obj.arr[-4]), None);
+/// ```
+///
+/// ## Finding Array Element by Field
+/// ```
+/// use serde_json::json;
+/// use valq::query_value;
+///
+/// let obj = json!({"users": [{ "id": 1, "email": "alice@example.com" }, { "id": 2, "email": "bob@example.com" }]});
+/// let user_id = 2;
+///
+/// // the first element of `users` whose `id` equals to `user_id`
+/// assert_eq!(query_value!(obj.users[id = user_id].email -> str), Some("bob@example.com"));
+/// ```
+///
+/// ## Finding Object Entry (or Array Element) by Value
+/// ```
+/// use serde_json::json;
+/// use valq::query_value;
+///
+/// let obj = json!({"services": {"web": { "port": 80, "name": "nginx" }, "api": { "port": 8080, "name": "axum" }}});
+///
+/// // the value of the first entry of `services` whose `port` equals to 8080
+/// assert_eq!(query_value!(obj.services[? .port == 8080].name -> str), Some("axum"));
+///
+/// // elements of arrays are searched as well
+/// let users = json!([{ "name": "alice", "active": false }, { "name": "bob", "active": true }]);
+/// assert_eq!(query_value!(users[? .active == true].name -> str), Some("bob"));
+/// ```
+///
+/// ## Slicing Array
+/// ```
+/// use serde_json::{json, Value};
+/// use valq::query_value;
+///
+/// let mut obj = json!({"arr": [0, 1, 2, 3, 4]});
+///
+/// // a range in brackets at the end of the query results in a slice of the array
+/// let page: &[Value] = query_value!(obj.arr[1..3]).unwrap();
+/// assert_eq!(page, &[json!(1), json!(2)]);
+/// assert_eq!(query_value!(obj.arr[3..]).unwrap().len(), 2);
+///
+/// // mutable slices with `mut`
+/// query_value!(mut obj.arr[..2]).unwrap().fill(json!(null));
+/// assert_eq!(obj["arr"], json!([null, null, 2, 3, 4]));
+/// ```
+///
+/// ## Treating Null as Missing
+/// ```
+/// use serde_json::json;
+/// use valq::query_value;
+///
+/// let obj = json!({"profile": null});
+///
+/// // `?` after a segment makes the query fail if the value at the segment is null
+/// assert_eq!(query_value!(obj.profile?.name), None);
+/// assert_eq!(query_value!(obj.profile?), None);
+/// // without `?`, null is a value like any other
+/// assert_eq!(query_value!(obj.profile), Some(&json!(null)));
+/// ```
+///
 /// ## Converting to Specified Type
 /// ```ignore
 /// // try to convert extracted value to `u64` by `as_u64()` method  on that value.
 /// // results in `None` in case of type mismatch
 /// let foo_u64: Option<u64> = query_value!(obj.foo -> u64)
 ///
+/// // `-> i128` / `-> u128` accept whole range of both `i64` and `u64` (by trying `as_i64()` then `as_u64()`) without going through `f64`.
+/// let big: Option<i128> = query_value!(obj.big -> i128)
+///
+/// // `-> u8` / `-> u16` / `-> u32` / `-> usize` (and signed counterparts) are converted from `as_u64()` (`as_i64()`) with range checking.
+/// // results in `None` if the value doesn't fit in the type.
+/// let port: Option<u16> = query_value!(obj.port -> u16)
+///
+/// // `-> display` borrows strings as they are, and renders other values by `to_string()`, resulting in `Cow<str>`.
+/// let id: Option<Cow<str>> = query_value!(obj.id -> display)
+///
+/// // `-> enum(T)` parses a string into `T` by `FromStr` (e.g. discriminators into your own enum), without going through deserialization.
+/// let status: Option<Status> = query_value!(obj.status -> enum(Status))
+///
+/// // `-> method()` calls the method of that name as it is, for value types with their own naming of accessors.
+/// // the method must return `Option<_>`.
+/// let name: Option<&str> = query_value!(obj.name -> get_str())
+///
 /// // in case of mutable reference extraction (see below), `as_xxx_mut()` method will be used.
 /// let arr_vec: Option<&mut Vec<Value>> = query_value!(mut obj.arr -> array)
 /// ```
@@ -62,7 +438,7 @@
 /// # Query Syntax
 ///
 /// ```txt
-/// query_value!(("mut")? <value> ("." <key> | "[" <idx> "]")+ ("->" <to_type>)?)
+/// query_value!(("mut")? <value> (("." <key> | "[" "-"? <idx> "]" | "[" <key> "=" <expr> "]" | "[?" <query>? "==" <expr> "]") "?"?)+ ("[" <range> "]" | "->" (<to_type> | <method> "()"))?)
 /// ```
 ///
 /// where:
@@ -72,12 +448,30 @@
 ///     + Any identifiers or `str` literals can be used. You may want to use `str` literals to get property keyed by a string that is invalid identifier in Rust (e.g. starts with digits).
 /// - `<idx>`: An index of array-like stracture to extract
 ///     + Any expressions evaluates to integer value can be used.
+/// - `[-<idx>]`: Selects an element of array-like structure counting from the end (`[-1]` for the last element, `[-2]` for the second-to-last, ...)
+///     + The index must be written with a leading `-` (e.g. `[-1]`, `[-n]`, `[-(n + 1)]`), and the whole expression is evaluated as a signed integer (`i64`), so variables in it must be of signed integer types.
+///       A non-negative result (e.g. `[-n]` with `n < 0`) indexes from the start as usual.
+///     + Available for types implementing [`Editable`](crate::Editable).
+/// - `[<key> = <expr>]`: Selects the first element of array-like structure whose field `<key>` equals to `<expr>` (e.g. `users[id = user_id]`)
+///     + Compared by `==` between the field value and `<expr>`, so `Value` must implement `PartialEq` for the type of `<expr>` (as `serde_json::Value` does for `&str`, `u64`, `bool`, ...).
+/// - `[? <query> == <expr>]`: Selects the first child (the value of an entry of object, or an element of array-like structure) whose value at `<query>` (e.g. `.port`, or empty for the value itself) equals to `<expr>`
+///     + Matches the same children as in [`for_each_value_mut`](crate::for_each_value_mut) and [`update_where`](crate::update_where), which visit all of them instead of the first one.
+///     + Available for types implementing [`Editable`](crate::Editable). Use [`Editable::find_entry`](crate::Editable::find_entry) directly if you need the key of the entry as well.
+/// - `?` after a segment: Makes the query fail (result in `None`) if the value at the segment is null, rather than going on into the null
+///     + Available for types implementing [`Editable`](crate::Editable).
+/// - `[<range>]`: Selects a slice of array-like structure by a range (`lo..hi`, `lo..`, `..hi`, `..`, or `..=` variants), resulting in `&[Value]` (`&mut [Value]` for `mut` queries)
+///     + Can only be at the end of a query. The start bound must be a single token (a literal, an identifier, or a parenthesized expression).
+///     + Results in `None` if the range is out of bounds. Available for types implementing [`Editable`](crate::Editable).
 /// - `<to_type>`: A name of "type" queried value should be converted to
+/// - `<method>`: A name of conversion method of `Value` to be called as it is
 ///
 /// # Compatibility
 /// This macro can be used with arbitrary data structure(to call, `Value`) that supports `get(&self, idx) -> Option<&Value>` method that retrieves a value at `idx`(can be string (retrieving "property"/"field"), or integer (indexing "array"/"sequence")).
 ///
 /// Type conversion query `-> xxx` is available if `Value` has conversion method `as_xxx(&self) -> Option<X>`/`as_xxx_mut(&mut self) -> Option<X>`.
+/// `-> i128` / `-> u128` are available if `Value` has `as_i64()` and `as_u64()`, narrower integer targets (`-> u32`, `-> i16`, ...) are available if `Value` has `as_u64()` (for unsigned ones) or `as_i64()` (for signed ones), `-> display` is available if `Value` has `as_str()` and implements `Display`, and `-> enum(T)` is available if `Value` has `as_str()` (for non-`mut` queries).
+/// `-> method()` is available if `Value` has a method `method(&self) -> Option<X>` (or `method(&mut self)` for `mut` queries).
+/// For any other target type `T`, the conversion is done by [`ValueAs<T>`](crate::ValueAs) implemented for `Value`, so you can add your own conversions (for non-`mut` queries).
 ///
 /// Extracting mutable reference is also supported when `Value` supports `get_mut(&mut self, idx) -> Option<&Value>`.
 ///
@@ -88,23 +482,96 @@
 /// - [`toml::Value`](https://docs.rs/toml/latest/toml/value/enum.Value.html)
 /// - and more...
 ///
+/// The root `<value>` doesn't have to be of the same type as the values inside it, as long as it supports `get`/`get_mut`.
+/// For example, you can start querying from maps of values such as `serde_json::Map`, `HashMap<String, Value>` and `BTreeMap<String, Value>` (with `.<key>` as the first segment),
+/// or from sequences of values such as `Vec<Value>` and `&[Value]` (with `[<idx>]` as the first segment).
+///
+/// The same capabilities are formally defined as the [`Queryable`](crate::Queryable) trait, which is required by the runtime query API ([`Query`](crate::Query)) instead.
+///
 #[macro_export]
 macro_rules! query_value {
     /* non-mut traversal */
-    (@trv { $vopt:expr }) => {
-        $vopt
+    (@trv [$l:lifetime] $cur:tt) => {
+        Some($cur)
+    };
+    (@trv [$l:lifetime] $cur:tt -> $method:ident()) => {
+        $cur.$method()
+    };
+    (@trv [$l:lifetime] $cur:tt -> enum($to:ty)) => {
+        $cur.as_str().and_then(|s| s.parse::<$to>().ok())
+    };
+    (@trv [$l:lifetime] $cur:tt -> $to:ident) => {
+        $crate::query_value!(@conv $cur, $to)
+    };
+    (@trv [$l:lifetime] $cur:tt . $key:ident $($rest:tt)*) => {
+        let Some(v) = $cur.get(stringify!($key)) else { break $l None };
+        $crate::query_value! { @trv [$l] v $($rest)* }
+    };
+    (@trv [$l:lifetime] $cur:tt . $key:literal $($rest:tt)*) => {
+        let Some(v) = $cur.get($key as &str) else { break $l None };
+        $crate::query_value! { @trv [$l] v $($rest)* }
+    };
+    (@trv [$l:lifetime] $cur:tt .. $($_:tt)*) => {
+        compile_error!("`..` selects multiple values, which a query resulting in `Option` can't, use query_all!() to collect all of them")
+    };
+    (@trv [$l:lifetime] $cur:tt [*] $($rest:tt)*) => {
+        compile_error!("`[*]` selects multiple values, which a query resulting in `Option` can't, use query_all!() to collect all of them")
     };
-    (@trv { $vopt:expr } -> $to:ident) => {
-        $vopt.and_then(|v| query_value!(@conv v, $to))
+    (@trv [$l:lifetime] $cur:tt [ ? $($pred:tt)+ ] $($rest:tt)*) => {
+        let Some(pos) = $crate::query_value!(@pred_pos $cur [] [$($pred)+]) else { break $l None };
+        let Some(v) = $crate::query_value!(@get_child $cur, &pos, get) else { break $l None };
+        $crate::query_value! { @trv [$l] v $($rest)* }
     };
-    (@trv { $vopt:expr } . $key:ident $($rest:tt)*) => {
-        query_value!(@trv { $vopt.and_then(|v| v.get(stringify!($key))) } $($rest)*)
+    // negative index `[-n]` counts from the end of the array
+    (@trv [$l:lifetime] $cur:tt [ - $($n:tt)+ ] $($rest:tt)*) => {
+        let Some(v) = $crate::query_value!(@neg_idx $cur, - $($n)+).and_then(|i| $cur.get(i)) else { break $l None };
+        $crate::query_value! { @trv [$l] v $($rest)* }
     };
-    (@trv { $vopt:expr } . $key:literal $($rest:tt)*) => {
-        query_value!(@trv { $vopt.and_then(|v| v.get($key as &str)) } $($rest)*)
+    (@trv [$l:lifetime] $cur:tt [ $field:ident = $val:expr ] $($rest:tt)*) => {
+        $crate::query_value! { @trv [$l] $cur [ stringify!($field) => $val ] $($rest)* }
     };
-    (@trv { $vopt:expr } [ $idx:expr ] $($rest:tt)*) => {
-        query_value!(@trv { $vopt.and_then(|v| v.get($idx as usize)) } $($rest)*)
+    (@trv [$l:lifetime] $cur:tt [ $field:literal = $val:expr ] $($rest:tt)*) => {
+        $crate::query_value! { @trv [$l] $cur [ $field => $val ] $($rest)* }
+    };
+    (@trv [$l:lifetime] $cur:tt [ $field:expr => $val:expr ] $($rest:tt)*) => {
+        let Some(v) = $crate::query_value!(@field_pos $cur, $field, $val).and_then(|i| $cur.get(i)) else { break $l None };
+        $crate::query_value! { @trv [$l] v $($rest)* }
+    };
+    // slices `[lo..hi]`, `[..hi]`, `[lo..]`, `[..]` (and `..=`) at the end of the query
+    (@trv [$l:lifetime] $cur:tt [ .. $($hi:tt)* ]) => {
+        $crate::query_value!(@slice_get $cur, .. $($hi)*)
+    };
+    (@trv [$l:lifetime] $cur:tt [ ..= $($hi:tt)+ ]) => {
+        $crate::query_value!(@slice_get $cur, ..= $($hi)+)
+    };
+    (@trv [$l:lifetime] $cur:tt [ $lo:tt .. $($hi:tt)* ]) => {
+        $crate::query_value!(@slice_get $cur, $lo .. $($hi)*)
+    };
+    (@trv [$l:lifetime] $cur:tt [ $lo:tt ..= $($hi:tt)+ ]) => {
+        $crate::query_value!(@slice_get $cur, $lo ..= $($hi)+)
+    };
+    (@trv [$l:lifetime] $cur:tt [ .. $($hi:tt)* ] $($rest:tt)+) => {
+        compile_error!("slice segment `[<range>]` must be at the end of the query")
+    };
+    (@trv [$l:lifetime] $cur:tt [ $lo:tt .. $($hi:tt)* ] $($rest:tt)+) => {
+        compile_error!("slice segment `[<range>]` must be at the end of the query")
+    };
+    (@trv [$l:lifetime] $cur:tt [ ..= $($hi:tt)* ] $($rest:tt)+) => {
+        compile_error!("slice segment `[<range>]` must be at the end of the query")
+    };
+    (@trv [$l:lifetime] $cur:tt [ $lo:tt ..= $($hi:tt)* ] $($rest:tt)+) => {
+        compile_error!("slice segment `[<range>]` must be at the end of the query")
+    };
+    (@trv [$l:lifetime] $cur:tt [ $idx:expr ] $($rest:tt)*) => {
+        let Some(v) = $cur.get($idx as usize) else { break $l None };
+        $crate::query_value! { @trv [$l] v $($rest)* }
+    };
+    (@trv [$l:lifetime] $cur:tt ? $($rest:tt)*) => {
+        if {
+            use $crate::__private::Reborrow as _;
+            $crate::Editable::is_null($cur.reborrow())
+        } { break $l None }
+        $crate::query_value! { @trv [$l] $cur $($rest)* }
     };
     (@trv $($_:tt)*) => {
         compile_error!("invalid query syntax for query_value!()")
@@ -123,6 +590,46 @@ macro_rules! query_value {
     (@conv $v:expr, f64) => {
         $v.as_f64()
     };
+    (@conv $v:expr, i128) => {
+        match $v.as_i64() {
+            Some(n) => Some(i128::from(n)),
+            None => $v.as_u64().map(i128::from),
+        }
+    };
+    (@conv $v:expr, u128) => {
+        $v.as_u64().map(u128::from)
+    };
+    // narrower integers: converted from `as_u64()`/`as_i64()` with range checking
+    (@conv $v:expr, u8) => {
+        $crate::query_value!(@conv_int $v, as_u64, u8)
+    };
+    (@conv $v:expr, u16) => {
+        $crate::query_value!(@conv_int $v, as_u64, u16)
+    };
+    (@conv $v:expr, u32) => {
+        $crate::query_value!(@conv_int $v, as_u64, u32)
+    };
+    (@conv $v:expr, usize) => {
+        $crate::query_value!(@conv_int $v, as_u64, usize)
+    };
+    (@conv $v:expr, i8) => {
+        $crate::query_value!(@conv_int $v, as_i64, i8)
+    };
+    (@conv $v:expr, i16) => {
+        $crate::query_value!(@conv_int $v, as_i64, i16)
+    };
+    (@conv $v:expr, i32) => {
+        $crate::query_value!(@conv_int $v, as_i64, i32)
+    };
+    (@conv $v:expr, isize) => {
+        $crate::query_value!(@conv_int $v, as_i64, isize)
+    };
+    (@conv $v:expr, display) => {
+        Some(match $v.as_str() {
+            Some(s) => ::std::borrow::Cow::Borrowed(s),
+            None => ::std::borrow::Cow::Owned($v.to_string()),
+        })
+    };
     (@conv $v:expr, bool) => {
         $v.as_bool()
     };
@@ -135,6 +642,10 @@ macro_rules! query_value {
     (@conv $v:expr, array) => {
         $v.as_array()
     };
+    // for serde_json::Value
+    (@conv $v:expr, number) => {
+        $v.as_number()
+    };
     // for serde_yaml::Value
     (@conv $v:expr, mapping) => {
         $v.as_mapping()
@@ -155,25 +666,104 @@ macro_rules! query_value {
     (@conv $v:expr, table) => {
         $v.as_table()
     };
+    // user-defined conversions
     (@conv $v:expr, $to:ident) => {
-        compile_error!(concat!("unsupported target type `", stringify!($to), "` is specified in query_value!()"))
+        $crate::ValueAs::<$to>::value_as($v)
+    };
+
+    (@conv_int $v:expr, $as:ident, $to:ty) => {
+        $v.$as().and_then(|n| <$to as ::std::convert::TryFrom<_>>::try_from(n).ok())
     };
 
+    // resolves the index of a negative index segment `[-n]` into the index from the start of the array, also used by other macros
+    (@neg_idx $cur:tt, $($idx:tt)+) => {{
+        use $crate::__private::Reborrow as _;
+        $crate::Editable::elements($cur.reborrow())
+            .and_then(|elems| $crate::__private::resolve_index(elems.len(), ($($idx)+) as i64))
+    }};
+
+    (@slice_get $cur:tt, $range:expr) => {{
+        use $crate::__private::Reborrow as _;
+        $crate::Editable::elements($cur.reborrow()).and_then(|elems| elems.get($range))
+    }};
+
     /* mut traversal */
-    (@trv_mut { $vopt:expr }) => {
-        $vopt
+    (@trv_mut [$l:lifetime] $cur:tt) => {
+        Some($cur)
+    };
+    (@trv_mut [$l:lifetime] $cur:tt -> $method:ident()) => {
+        $cur.$method()
+    };
+    (@trv_mut [$l:lifetime] $cur:tt -> $to:ident) => {
+        $crate::query_value!(@conv_mut $cur, $to)
+    };
+    (@trv_mut [$l:lifetime] $cur:tt . $key:ident $($rest:tt)*) => {
+        let Some(v) = $cur.get_mut(stringify!($key)) else { break $l None };
+        $crate::query_value! { @trv_mut [$l] v $($rest)* }
     };
-    (@trv_mut { $vopt:expr } -> $to:ident) => {
-        $vopt.and_then(|v| query_value!(@conv_mut v, $to))
+    (@trv_mut [$l:lifetime] $cur:tt . $key:literal $($rest:tt)*) => {
+        let Some(v) = $cur.get_mut($key as &str) else { break $l None };
+        $crate::query_value! { @trv_mut [$l] v $($rest)* }
     };
-    (@trv_mut { $vopt:expr } . $key:ident $($rest:tt)*) => {
-        query_value!(@trv_mut { $vopt.and_then(|v| v.get_mut(stringify!($key))) } $($rest)*)
+    (@trv_mut [$l:lifetime] $cur:tt [ ? $($pred:tt)+ ] $($rest:tt)*) => {
+        let Some(pos) = $crate::query_value!(@pred_pos $cur [] [$($pred)+]) else { break $l None };
+        let Some(v) = $crate::query_value!(@get_child $cur, &pos, get_mut) else { break $l None };
+        $crate::query_value! { @trv_mut [$l] v $($rest)* }
     };
-    (@trv_mut { $vopt:expr } . $key:literal $($rest:tt)*) => {
-        query_value!(@trv_mut { $vopt.and_then(|v| v.get_mut($key as &str)) } $($rest)*)
+    (@trv_mut [$l:lifetime] $cur:tt [ - $($n:tt)+ ] $($rest:tt)*) => {
+        let Some(i) = $crate::query_value!(@neg_idx $cur, - $($n)+) else { break $l None };
+        let Some(v) = $cur.get_mut(i) else { break $l None };
+        $crate::query_value! { @trv_mut [$l] v $($rest)* }
     };
-    (@trv_mut { $vopt:expr } [ $idx:expr ] $($rest:tt)*) => {
-        query_value!(@trv_mut { $vopt.and_then(|v| v.get_mut($idx as usize)) } $($rest)*)
+    (@trv_mut [$l:lifetime] $cur:tt [*] $($rest:tt)*) => {
+        compile_error!("`[*]` is not supported in `mut` queries, use for_each_value_mut!() instead")
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ $field:ident = $val:expr ] $($rest:tt)*) => {
+        $crate::query_value! { @trv_mut [$l] $cur [ stringify!($field) => $val ] $($rest)* }
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ $field:literal = $val:expr ] $($rest:tt)*) => {
+        $crate::query_value! { @trv_mut [$l] $cur [ $field => $val ] $($rest)* }
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ $field:expr => $val:expr ] $($rest:tt)*) => {
+        let Some(i) = $crate::query_value!(@field_pos $cur, $field, $val) else { break $l None };
+        let Some(v) = $cur.get_mut(i) else { break $l None };
+        $crate::query_value! { @trv_mut [$l] v $($rest)* }
+    };
+    // slices `[lo..hi]`, `[..hi]`, `[lo..]`, `[..]` (and `..=`) at the end of the query
+    (@trv_mut [$l:lifetime] $cur:tt [ .. $($hi:tt)* ]) => {
+        $crate::query_value!(@slice_get_mut $cur, .. $($hi)*)
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ ..= $($hi:tt)+ ]) => {
+        $crate::query_value!(@slice_get_mut $cur, ..= $($hi)+)
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ $lo:tt .. $($hi:tt)* ]) => {
+        $crate::query_value!(@slice_get_mut $cur, $lo .. $($hi)*)
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ $lo:tt ..= $($hi:tt)+ ]) => {
+        $crate::query_value!(@slice_get_mut $cur, $lo ..= $($hi)+)
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ .. $($hi:tt)* ] $($rest:tt)+) => {
+        compile_error!("slice segment `[<range>]` must be at the end of the query")
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ $lo:tt .. $($hi:tt)* ] $($rest:tt)+) => {
+        compile_error!("slice segment `[<range>]` must be at the end of the query")
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ ..= $($hi:tt)* ] $($rest:tt)+) => {
+        compile_error!("slice segment `[<range>]` must be at the end of the query")
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ $lo:tt ..= $($hi:tt)* ] $($rest:tt)+) => {
+        compile_error!("slice segment `[<range>]` must be at the end of the query")
+    };
+    (@trv_mut [$l:lifetime] $cur:tt [ $idx:expr ] $($rest:tt)*) => {
+        let Some(v) = $cur.get_mut($idx as usize) else { break $l None };
+        $crate::query_value! { @trv_mut [$l] v $($rest)* }
+    };
+    (@trv_mut [$l:lifetime] $cur:tt ? $($rest:tt)*) => {
+        if {
+            use $crate::__private::Reborrow as _;
+            $crate::Editable::is_null($cur.reborrow())
+        } { break $l None }
+        $crate::query_value! { @trv_mut [$l] $cur $($rest)* }
     };
     (@trv_mut $($_:tt)*) => {
         compile_error!("invalid query syntax for query_value!()")
@@ -204,203 +794,2921 @@ macro_rules! query_value {
         compile_error!(concat!("unsupported target type `", stringify!($to), "` is specified in query_value!()"))
     };
 
+    (@slice_get_mut $cur:tt, $range:expr) => {{
+        use $crate::__private::ReborrowMut as _;
+        $crate::Editable::elements_mut($cur.reborrow_mut()).and_then(|elems| elems.get_mut($range))
+    }};
+
+    /* resolving segments selecting a child by its value, also used by other macros */
+    // `[? <query> == <expr>]`: the first child (the value of an entry of an object, or an element of an array) whose value at `<query>` equals to `<expr>`
+    (@pred_pos $cur:tt [] [== $val:expr]) => {{
+        use $crate::__private::Reborrow as _;
+        let want = &$val;
+        $crate::__private::find_child($cur.reborrow(), |e| *e == *want)
+    }};
+    (@pred_pos $cur:tt [$($q:tt)+] [== $val:expr]) => {{
+        use $crate::__private::Reborrow as _;
+        let want = &$val;
+        $crate::__private::find_child($cur.reborrow(), |e| $crate::query_value!(e $($q)+).is_some_and(|f| *f == *want))
+    }};
+    (@pred_pos $cur:tt [$($q:tt)*] [$t:tt $($p:tt)*]) => {
+        $crate::query_value!(@pred_pos $cur [$($q)* $t] [$($p)*])
+    };
+    (@pred_pos $cur:tt [$($q:tt)*] []) => {
+        compile_error!("predicate of `[? ...]` must be in the form of `<query> == <expr>`")
+    };
+    // `[<field> = <expr>]`: index of the first element of an array whose field `<field>` equals to `<expr>`
+    (@field_pos $cur:tt, $field:expr, $val:expr) => {{
+        let want = &$val;
+        (0..)
+            .map_while(|i: usize| $cur.get(i))
+            .position(|e| e.get($field as &str).is_some_and(|f| *f == *want))
+    }};
+    (@get_child $cur:tt, $pos:expr, $get:ident) => {
+        match $pos {
+            $crate::__private::ChildPos::Key(k) => $cur.$get(k.as_str()),
+            $crate::__private::ChildPos::Index(i) => $cur.$get(*i),
+        }
+    };
+
     /* entry point */
+    // Each query expands to a single labeled block that bails out with `None` on the first missing segment,
+    // rather than a chain of `Option::and_then` closures (which is slow in debug builds and costly to compile; see `benches/compile_time.sh`).
+    ($v:tt .. $($rest:tt)+) => {
+        compile_error!("`..` selects multiple values, which a query resulting in `Option` can't, use query_all!() to collect all of them")
+    };
     ($v:tt . $key:ident $($rest:tt)*) => {
-        query_value!(@trv { $v.get(stringify!($key)) } $($rest)*)
+        'query: { $crate::query_value! { @trv ['query] $v . $key $($rest)* } }
     };
     ($v:tt . $key:literal $($rest:tt)*) => {
-        query_value!(@trv { $v.get($key as &str) } $($rest)*)
+        'query: { $crate::query_value! { @trv ['query] $v . $key $($rest)* } }
     };
-    ($v:tt [ $idx:expr ] $($rest:tt)*) => {
-        query_value!(@trv { $v.get($idx as usize) } $($rest)*)
+    ($v:tt [ $($seg:tt)+ ] $($rest:tt)*) => {
+        'query: { $crate::query_value! { @trv ['query] $v [ $($seg)+ ] $($rest)* } }
+    };
+    (mut $v:tt .. $($rest:tt)+) => {
+        compile_error!("`..` is not supported in `mut` queries")
     };
     (mut $v:tt . $key:ident $($rest:tt)*) => {
-        query_value!(@trv_mut { $v.get_mut(stringify!($key)) } $($rest)*)
+        'query: { $crate::query_value! { @trv_mut ['query] $v . $key $($rest)* } }
     };
     (mut $v:tt . $key:literal $($rest:tt)*) => {
-        query_value!(@trv_mut { $v.get_mut($key as &str) } $($rest)*)
+        'query: { $crate::query_value! { @trv_mut ['query] $v . $key $($rest)* } }
     };
-    (mut $v:tt [ $idx:expr ] $($rest:tt)*) => {
-        query_value!(@trv_mut { $v.get_mut($idx as usize) } $($rest)*)
+    (mut $v:tt [ $($seg:tt)+ ] $($rest:tt)*) => {
+        'query: { $crate::query_value! { @trv_mut ['query] $v [ $($seg)+ ] $($rest)* } }
     };
 }
 
-#[cfg(test)]
-mod tests {
-    use super::query_value;
-    macro_rules! test_is_some_of_expected_val {
-        ($tests:expr) => {
-            for (res, exp) in $tests {
-                if let Some(act) = res {
-                    assert_eq!(act, &exp)
-                } else {
-                    panic!("expect Some(...) but actually None")
-                }
+/// A macro for collecting all values matching a query, which can include segments selecting multiple values.
+///
+/// Takes the same query syntax as [`query_value`] (except for `mut`) plus the following segments, and results in a `Vec` of all values matching the query (empty if nothing matched), in document order.
+///
+/// - `[*]`: Selects every element of array-like structure
+/// - `..<key>`: Selects values of `<key>` in the current value and all values nested in it (i.e. recursive descent)
+///     + Available for types implementing [`Editable`](crate::Editable).
+///
+/// Conversions by `-> <to_type>` are applied to each match, and matches failing the conversion are left out.
+/// Use [`for_each_value_mut`] to mutate all matches.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::query_all;
+///
+/// let obj = json!({"items": [{ "name": "foo" }, { "name": "bar" }, { "id": 3 }]});
+///
+/// let names: Vec<&str> = query_all!(obj.items[*].name -> str);
+/// assert_eq!(names, vec!["foo", "bar"]);
+///
+/// let obj = json!({"id": 1, "children": [{ "id": 2 }, { "meta": { "id": 3 } }]});
+///
+/// let ids: Vec<u64> = query_all!(obj..id -> u64);
+/// assert_eq!(ids, vec![1, 2, 3]);
+/// ```
+///
+/// Since their result is a `Vec` rather than an `Option`, these segments are rejected by [`query_value`] and other macros built on it (e.g. [`try_query`]):
+///
+/// ```compile_fail
+/// # use serde_json::json;
+/// # use valq::query_value;
+/// let obj = json!({"items": [{ "name": "foo" }]});
+/// let names = query_value!(obj.items[*].name);
+/// ```
+#[macro_export]
+macro_rules! query_all {
+    /* traversal collecting all matches of query with wildcard */
+    (@all [$acc:ident] $cur:tt) => {
+        $acc.push($cur);
+    };
+    (@all [$acc:ident] $cur:tt -> $($conv:tt)+) => {
+        #[allow(unused_labels)]
+        let converted = 'conv: { $crate::query_value! { @trv ['conv] $cur -> $($conv)+ } };
+        if let Some(v) = converted {
+            $acc.push(v);
+        }
+    };
+    (@all [$acc:ident] $cur:tt [*] $($rest:tt)*) => {
+        for v in (0..).map_while(|i: usize| $cur.get(i)) {
+            $crate::query_all! { @all [$acc] v $($rest)* }
+        }
+    };
+    (@all [$acc:ident] $cur:tt .. $key:tt $($rest:tt)*) => {
+        let descendants = {
+            use $crate::__private::Reborrow as _;
+            $crate::__private::descendants($cur.reborrow())
+        };
+        for d in descendants {
+            $crate::query_all! { @all_seg [$acc] d [. $key] $($rest)* }
+        }
+    };
+    // other segments are resolved one by one by the usual traversal
+    (@all [$acc:ident] $cur:tt . $key:tt $($rest:tt)*) => {
+        $crate::query_all! { @all_seg [$acc] $cur [. $key] $($rest)* }
+    };
+    (@all [$acc:ident] $cur:tt [ $($seg:tt)+ ] $($rest:tt)*) => {
+        $crate::query_all! { @all_seg [$acc] $cur [[ $($seg)+ ]] $($rest)* }
+    };
+    (@all [$acc:ident] $cur:tt ? $($rest:tt)*) => {
+        $crate::query_all! { @all_seg [$acc] $cur [?] $($rest)* }
+    };
+    (@all $($_:tt)*) => {
+        compile_error!("invalid query syntax for query_all!()")
+    };
+    (@all_seg [$acc:ident] $cur:tt [$($seg:tt)+] $($rest:tt)*) => {
+        if let Some(v) = 'seg: { $crate::query_value! { @trv ['seg] $cur $($seg)+ } } {
+            $crate::query_all! { @all [$acc] v $($rest)* }
+        }
+    };
+
+    /* entry point */
+    (mut $($_:tt)*) => {
+        compile_error!("`mut` is not supported in query_all!(), use for_each_value_mut!() instead")
+    };
+    ($v:tt $($rest:tt)+) => {{
+        let mut all = Vec::new();
+        $crate::query_all! { @all [all] $v $($rest)+ }
+        all
+    }};
+}
+
+/// A macro for querying inner value of structured data, and returning an owned copy of it.
+///
+/// Takes the same query syntax as [`query_value`] (except for `mut`), and clones the result by [`ToOwned::to_owned`] (or [`Cow::into_owned`](std::borrow::Cow::into_owned) for `-> display`, resulting in `String`).
+/// It comes in handy when the result must outlive the queried data, e.g. sending it to other threads or storing it in caches.
+///
+/// ```
+/// use serde_json::{json, Value};
+/// use valq::query_value_owned;
+///
+/// let obj = json!({"foo": { "bar": { "x": 1, "y": 2 }, "name": "foo" }});
+///
+/// let bar: Option<Value> = query_value_owned!(obj.foo.bar);
+/// let name: Option<String> = query_value_owned!(obj.foo.name -> str);
+/// drop(obj);
+///
+/// assert_eq!(bar, Some(json!({ "x": 1, "y": 2 })));
+/// assert_eq!(name, Some("foo".to_string()));
+/// ```
+#[macro_export]
+macro_rules! query_value_owned {
+    // `Cow` from `-> display` has to be turned into `String`, since `to_owned()` on it just clones the borrowing `Cow`
+    (@owned [$($query:tt)+] -> display) => {
+        $crate::query_value!($($query)+ -> display).map(::std::borrow::Cow::into_owned)
+    };
+    (@owned [$($query:tt)+]) => {
+        $crate::query_value!($($query)+).map(|v| v.to_owned())
+    };
+    (@owned [$($query:tt)+] $t:tt $($rest:tt)*) => {
+        $crate::query_value_owned!(@owned [$($query)+ $t] $($rest)*)
+    };
+
+    /* entry point */
+    ($v:tt $($rest:tt)+) => {
+        $crate::query_value_owned!(@owned [$v] $($rest)+)
+    };
+}
+
+/// A macro for querying inner value of structured data, along with the path to it.
+///
+/// Takes the same query syntax as [`query_value`] (except for slices), and returns the result paired with the path to the queried value, rendered in the query syntax (e.g. `.foo.arr[1]`).
+/// Segments are rendered with the index or key actually selected (e.g. `[-1]`, `[id = 2]` and `[? ...]` as `[2]` or `.key`), so it tells exactly which value is extracted (or modified, if combined with `mut`).
+///
+/// ```
+/// use serde_json::{json, Value};
+/// use valq::query_value_with_path;
+///
+/// let mut obj = json!({"users": [{ "name": "alice" }, { "name": "bob" }]});
+/// let i = 1;
+///
+/// let (name, path) = query_value_with_path!(mut obj.users[i].name).unwrap();
+/// *name = json!("carol");
+///
+/// assert_eq!(path, ".users[1].name");
+/// assert_eq!(obj, json!({"users": [{ "name": "alice" }, { "name": "carol" }]}));
+/// ```
+#[macro_export]
+macro_rules! query_value_with_path {
+    /* traversal */
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt) => {
+        Some(($cur, $path))
+    };
+    (@trv [$l:lifetime get] $path:ident $cur:tt -> $($conv:tt)+) => {
+        #[allow(unused_labels)]
+        let converted = 'conv: { $crate::query_value! { @trv ['conv] $cur -> $($conv)+ } };
+        match converted {
+            Some(v) => Some((v, $path)),
+            None => None,
+        }
+    };
+    (@trv [$l:lifetime get_mut] $path:ident $cur:tt -> $($conv:tt)+) => {
+        #[allow(unused_labels)]
+        let converted = 'conv: { $crate::query_value! { @trv_mut ['conv] $cur -> $($conv)+ } };
+        match converted {
+            Some(v) => Some((v, $path)),
+            None => None,
+        }
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt . $key:ident $($rest:tt)*) => {
+        let Some(v) = $cur.$get(stringify!($key)) else { break $l None };
+        $path.push_str(concat!(".", stringify!($key)));
+        $crate::query_value_with_path! { @trv [$l $get] $path v $($rest)* }
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt . $key:literal $($rest:tt)*) => {
+        let Some(v) = $cur.$get($key as &str) else { break $l None };
+        $path.push_str(concat!(".", stringify!($key)));
+        $crate::query_value_with_path! { @trv [$l $get] $path v $($rest)* }
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt .. $($_:tt)*) => {
+        compile_error!("`..` is not supported in query_value_with_path!(), since it selects multiple values")
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt [ ? $($pred:tt)+ ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@pred_pos $cur [] [$($pred)+]);
+        $crate::query_value_with_path! { @child [$l $get] $path $cur pos, $($rest)* }
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt [ - $($n:tt)+ ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@neg_idx $cur, - $($n)+).map($crate::__private::ChildPos::Index);
+        $crate::query_value_with_path! { @child [$l $get] $path $cur pos, $($rest)* }
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt [ $field:ident = $val:expr ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@field_pos $cur, stringify!($field), $val).map($crate::__private::ChildPos::Index);
+        $crate::query_value_with_path! { @child [$l $get] $path $cur pos, $($rest)* }
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt [ $field:literal = $val:expr ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@field_pos $cur, $field, $val).map($crate::__private::ChildPos::Index);
+        $crate::query_value_with_path! { @child [$l $get] $path $cur pos, $($rest)* }
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt [*] $($rest:tt)*) => {
+        compile_error!("`[*]` is not supported in query_value_with_path!(), since it selects multiple values")
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt [ .. $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in query_value_with_path!()")
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt [ $lo:tt .. $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in query_value_with_path!()")
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt [ ..= $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in query_value_with_path!()")
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt [ $lo:tt ..= $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in query_value_with_path!()")
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt [ $idx:expr ] $($rest:tt)*) => {
+        let pos = Some($crate::__private::ChildPos::Index($idx as usize));
+        $crate::query_value_with_path! { @child [$l $get] $path $cur pos, $($rest)* }
+    };
+    (@trv [$l:lifetime $get:ident] $path:ident $cur:tt ? $($rest:tt)*) => {
+        if {
+            use $crate::__private::Reborrow as _;
+            $crate::Editable::is_null($cur.reborrow())
+        } { break $l None }
+        $crate::query_value_with_path! { @trv [$l $get] $path $cur $($rest)* }
+    };
+    (@trv $($_:tt)*) => {
+        compile_error!("invalid query syntax for query_value_with_path!()")
+    };
+    (@child [$l:lifetime $get:ident] $path:ident $cur:tt $pos:ident, $($rest:tt)*) => {
+        let Some(pos) = $pos else { break $l None };
+        let Some(v) = $crate::query_value!(@get_child $cur, &pos, $get) else { break $l None };
+        pos.push_to(&mut $path);
+        $crate::query_value_with_path! { @trv [$l $get] $path v $($rest)* }
+    };
+
+    /* entry point */
+    (mut $v:tt $($rest:tt)+) => {
+        'query: {
+            let mut path = String::new();
+            $crate::query_value_with_path! { @trv ['query get_mut] path $v $($rest)+ }
+        }
+    };
+    ($v:tt $($rest:tt)+) => {
+        'query: {
+            let mut path = String::new();
+            $crate::query_value_with_path! { @trv ['query get] path $v $($rest)+ }
+        }
+    };
+}
+
+/// A macro for collecting every value along the path of a query.
+///
+/// Takes the same query syntax as [`query_value`] (except for `mut`, `-> <to_type>` and slices), and returns a `Vec` of pairs of the path to the value (in the same format as [`query_value_with_path`]) and the value itself,
+/// from the root (with the empty path `""`) to the queried value. If some segment of the query can't be resolved, values up to the last resolved one are returned.
+///
+/// The result is deliberately a `Vec` rather than a lazy iterator: the query expands into straight-line code that resolves all segments at once (as [`query_value`] does),
+/// and the number of values is bounded by the number of segments written in the query, so collecting them is cheap. Iterate it in reverse to walk up from the queried value.
+///
+/// ```
+/// use serde_json::{json, Value};
+/// use valq::{query_ancestors, query_value};
+///
+/// let obj = json!({"version": 1, "app": {"version": 2, "plugins": [{ "name": "foo" }]}, "lib": {"deps": []}});
+///
+/// // find the nearest enclosing value that has `version` field
+/// let nearest = |ancestors: Vec<(String, &Value)>| {
+///     ancestors
+///         .into_iter()
+///         .rev()
+///         .find(|(_, v)| query_value!(v.version).is_some())
+///         .map(|(path, _)| path)
+/// };
+///
+/// assert_eq!(nearest(query_ancestors!(obj.app.plugins[0].name)), Some(".app".to_string()));
+/// assert_eq!(nearest(query_ancestors!(obj.lib.deps)), Some("".to_string())); // the root
+/// ```
+#[macro_export]
+macro_rules! query_ancestors {
+    /* traversal */
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt) => {};
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt . $key:ident $($rest:tt)*) => {
+        let Some(v) = $cur.get(stringify!($key)) else { break $l };
+        $path.push_str(concat!(".", stringify!($key)));
+        $acc.push(($path.clone(), v));
+        $crate::query_ancestors! { @trv [$l $acc $path] v $($rest)* }
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt . $key:literal $($rest:tt)*) => {
+        let Some(v) = $cur.get($key as &str) else { break $l };
+        $path.push_str(concat!(".", stringify!($key)));
+        $acc.push(($path.clone(), v));
+        $crate::query_ancestors! { @trv [$l $acc $path] v $($rest)* }
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt .. $($_:tt)*) => {
+        compile_error!("`..` is not supported in query_ancestors!(), since it selects multiple values")
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt [ ? $($pred:tt)+ ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@pred_pos $cur [] [$($pred)+]);
+        $crate::query_ancestors! { @child [$l $acc $path] $cur pos, $($rest)* }
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt [ - $($n:tt)+ ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@neg_idx $cur, - $($n)+).map($crate::__private::ChildPos::Index);
+        $crate::query_ancestors! { @child [$l $acc $path] $cur pos, $($rest)* }
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt [ $field:ident = $val:expr ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@field_pos $cur, stringify!($field), $val).map($crate::__private::ChildPos::Index);
+        $crate::query_ancestors! { @child [$l $acc $path] $cur pos, $($rest)* }
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt [ $field:literal = $val:expr ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@field_pos $cur, $field, $val).map($crate::__private::ChildPos::Index);
+        $crate::query_ancestors! { @child [$l $acc $path] $cur pos, $($rest)* }
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt [*] $($rest:tt)*) => {
+        compile_error!("`[*]` is not supported in query_ancestors!(), since it selects multiple values")
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt [ .. $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in query_ancestors!()")
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt [ $lo:tt .. $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in query_ancestors!()")
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt [ ..= $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in query_ancestors!()")
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt [ $lo:tt ..= $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in query_ancestors!()")
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt [ $idx:expr ] $($rest:tt)*) => {
+        let pos = Some($crate::__private::ChildPos::Index($idx as usize));
+        $crate::query_ancestors! { @child [$l $acc $path] $cur pos, $($rest)* }
+    };
+    (@trv [$l:lifetime $acc:ident $path:ident] $cur:tt ? $($rest:tt)*) => {
+        if {
+            use $crate::__private::Reborrow as _;
+            $crate::Editable::is_null($cur.reborrow())
+        } { break $l }
+        $crate::query_ancestors! { @trv [$l $acc $path] $cur $($rest)* }
+    };
+    (@trv $($_:tt)*) => {
+        compile_error!("invalid query syntax for query_ancestors!()")
+    };
+    (@child [$l:lifetime $acc:ident $path:ident] $cur:tt $pos:ident, $($rest:tt)*) => {
+        let Some(pos) = $pos else { break $l };
+        let Some(v) = $crate::query_value!(@get_child $cur, &pos, get) else { break $l };
+        pos.push_to(&mut $path);
+        $acc.push(($path.clone(), v));
+        $crate::query_ancestors! { @trv [$l $acc $path] v $($rest)* }
+    };
+
+    /* entry point */
+    ($v:tt $($rest:tt)+) => {{
+        let root = &$v;
+        let mut ancestors = Vec::new();
+        let mut path = String::new();
+        'query: {
+            $crate::query_ancestors! { @trv ['query ancestors path] root $($rest)+ }
+        }
+        // inserted after the traversal so that `root` is coerced into the type of the other values (e.g. `&&Value` into `&Value`)
+        ancestors.insert(0, (String::new(), root));
+        ancestors
+    }};
+}
+
+/// A macro for explaining how a query is resolved step by step, for debugging queries that fail unexpectedly.
+///
+/// Takes the same query syntax as [`query_value`] (except for `mut` and slices), and returns a `String` with a line per segment (and conversion) of the query,
+/// each of which has the path to the segment (in the same format as [`query_value_with_path`]), whether it was resolved and the kind of the value found (see [`Editable::kind`]).
+/// When a segment isn't resolved, the line tells why: the kind of the parent if it can't have the child (e.g. an array was expected but an object was found), or the parent that lacks it otherwise.
+/// Steps after the first failure are reported as skipped. Needs [`Editable`].
+///
+/// ```
+/// use serde_json::json;
+/// use valq::explain_query;
+///
+/// let obj = json!({"a": {"b": [{"c": 1}]}});
+///
+/// assert_eq!(
+///     explain_query!(obj.a.b[0].c -> str),
+///     "\
+/// .a: found (object)
+/// .a.b: found (array)
+/// .a.b[0]: found (object)
+/// .a.b[0].c: found (number)
+/// -> str: conversion failed (found number)
+/// ",
+/// );
+/// assert_eq!(
+///     explain_query!(obj.a[0].c),
+///     "\
+/// .a: found (object)
+/// .a[0]: not found (expected array, found object)
+/// .a[0].c: skipped
+/// ",
+/// );
+/// assert_eq!(
+///     explain_query!(obj.a.b[1]),
+///     "\
+/// .a: found (object)
+/// .a.b: found (array)
+/// .a.b[1]: not found (in array of length 1)
+/// ",
+/// );
+/// ```
+#[macro_export]
+macro_rules! explain_query {
+    /* traversal */
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt) => {
+        let _ = $cur;
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt -> $($conv:tt)+) => {
+        #[allow(unused_labels)]
+        let converted = 'conv: { $crate::query_value! { @trv ['conv] $cur -> $($conv)+ } };
+        $trace.push_str(concat!("-> ", stringify!($($conv)+)));
+        if converted.is_some() {
+            $trace.push_str(": converted\n");
+        } else {
+            use $crate::__private::Reborrow as _;
+            $trace.push_str(&format!(": conversion failed (found {})\n", $crate::Editable::kind($cur.reborrow())));
+        }
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt . $key:ident $($rest:tt)*) => {
+        $path.push_str(concat!(".", stringify!($key)));
+        $crate::explain_query! { @step [$l $trace $path] $cur, &["object"], $cur.get(stringify!($key)), $($rest)* }
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt . $key:literal $($rest:tt)*) => {
+        $path.push_str(concat!(".", stringify!($key)));
+        $crate::explain_query! { @step [$l $trace $path] $cur, &["object"], $cur.get($key as &str), $($rest)* }
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt .. $($_:tt)*) => {
+        compile_error!("`..` is not supported in explain_query!(), since it selects multiple values")
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt [ ? $($pred:tt)+ ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@pred_pos $cur [] [$($pred)+]);
+        $crate::explain_query! { @child [$l $trace $path] $cur pos, &["object", "array"], concat!("[? ", stringify!($($pred)+), "]"), $($rest)* }
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt [ - $($n:tt)+ ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@neg_idx $cur, - $($n)+).map($crate::__private::ChildPos::Index);
+        $crate::explain_query! { @child [$l $trace $path] $cur pos, &["array"], concat!("[-", stringify!($($n)+), "]"), $($rest)* }
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt [ $field:ident = $val:expr ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@field_pos $cur, stringify!($field), $val).map($crate::__private::ChildPos::Index);
+        $crate::explain_query! { @child [$l $trace $path] $cur pos, &["array"], concat!("[", stringify!($field), " = ", stringify!($val), "]"), $($rest)* }
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt [ $field:literal = $val:expr ] $($rest:tt)*) => {
+        let pos = $crate::query_value!(@field_pos $cur, $field, $val).map($crate::__private::ChildPos::Index);
+        $crate::explain_query! { @child [$l $trace $path] $cur pos, &["array"], concat!("[", stringify!($field), " = ", stringify!($val), "]"), $($rest)* }
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt [*] $($rest:tt)*) => {
+        compile_error!("`[*]` is not supported in explain_query!(), since it selects multiple values")
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt [ .. $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in explain_query!()")
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt [ $lo:tt .. $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in explain_query!()")
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt [ ..= $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in explain_query!()")
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt [ $lo:tt ..= $($hi:tt)* ] $($rest:tt)*) => {
+        compile_error!("slice segment `[<range>]` is not supported in explain_query!()")
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt [ $idx:expr ] $($rest:tt)*) => {
+        let pos = Some($crate::__private::ChildPos::Index($idx as usize));
+        $crate::explain_query! { @child [$l $trace $path] $cur pos, &["array"], "", $($rest)* }
+    };
+    (@trv [$l:lifetime $trace:ident $path:ident] $cur:tt ? $($rest:tt)*) => {
+        $trace.push_str(&$path);
+        if {
+            use $crate::__private::Reborrow as _;
+            $crate::Editable::is_null($cur.reborrow())
+        } {
+            $trace.push_str("?: null\n");
+            $crate::explain_query!(@skipped $trace $path $($rest)*);
+            break $l;
+        }
+        $trace.push_str("?: not null\n");
+        $crate::explain_query! { @trv [$l $trace $path] $cur $($rest)* }
+    };
+    (@trv $($_:tt)*) => {
+        compile_error!("invalid query syntax for explain_query!()")
+    };
+    // `$written` is the segment as written, for reporting the segment when the child isn't found
+    (@child [$l:lifetime $trace:ident $path:ident] $cur:tt $pos:ident, $expected:expr, $written:expr, $($rest:tt)*) => {
+        match &$pos {
+            Some(pos) => pos.push_to(&mut $path),
+            None => $path.push_str($written),
+        }
+        $crate::explain_query! { @step [$l $trace $path] $cur, $expected, $pos.and_then(|pos| $crate::query_value!(@get_child $cur, &pos, get)), $($rest)* }
+    };
+    // `$expected` is the kinds of the parent in which the segment can find a child, for telling why it wasn't found
+    (@step [$l:lifetime $trace:ident $path:ident] $cur:tt, $expected:expr, $get:expr, $($rest:tt)*) => {
+        $trace.push_str(&$path);
+        let Some(v) = $get else {
+            use $crate::__private::Reborrow as _;
+            $trace.push_str(&format!(": not found ({})\n", $crate::__private::not_found_reason($cur.reborrow(), $expected)));
+            $crate::explain_query!(@skipped $trace $path $($rest)*);
+            break $l;
+        };
+        $trace.push_str(&format!(": found ({})\n", $crate::Editable::kind(v)));
+        $crate::explain_query! { @trv [$l $trace $path] v $($rest)* }
+    };
+
+    /* reporting skipped steps */
+    (@skipped $trace:ident $path:ident) => {};
+    (@skipped $trace:ident $path:ident -> $($conv:tt)+) => {
+        $trace.push_str(concat!("-> ", stringify!($($conv)+), ": skipped\n"));
+    };
+    (@skipped $trace:ident $path:ident ? $($rest:tt)*) => {
+        $crate::explain_query!(@skipped $trace $path $($rest)*);
+    };
+    (@skipped $trace:ident $path:ident . $key:tt $($rest:tt)*) => {
+        $path.push_str(concat!(".", stringify!($key)));
+        $crate::explain_query!(@skipped_seg $trace $path $($rest)*);
+    };
+    (@skipped $trace:ident $path:ident [ - $($n:tt)+ ] $($rest:tt)*) => {
+        $path.push_str(concat!("[-", stringify!($($n)+), "]"));
+        $crate::explain_query!(@skipped_seg $trace $path $($rest)*);
+    };
+    (@skipped $trace:ident $path:ident [ $($idx:tt)* ] $($rest:tt)*) => {
+        $path.push_str(concat!("[", stringify!($($idx)*), "]"));
+        $crate::explain_query!(@skipped_seg $trace $path $($rest)*);
+    };
+    (@skipped_seg $trace:ident $path:ident $($rest:tt)*) => {
+        $trace.push_str(&$path);
+        $trace.push_str(": skipped\n");
+        $crate::explain_query!(@skipped $trace $path $($rest)*);
+    };
+
+    /* entry point */
+    ($v:tt $($rest:tt)+) => {{
+        let mut trace = String::new();
+        let mut path = String::new();
+        'query: {
+            $crate::explain_query! { @trv ['query trace path] $v $($rest)+ }
+        }
+        trace
+    }};
+}
+
+/// A macro for converting a query into a [JSON Pointer](https://www.rfc-editor.org/rfc/rfc6901) string at compile time.
+///
+/// The result is a `&'static str`, with `~` and `/` in keys escaped as `~0` and `~1`.
+/// Only keys (`.key` / `."key"`) and unsuffixed non-negative integer literal indices (`[0]`) are allowed in the query, since the pointer is assembled at compile time.
+/// The root of the query isn't evaluated, so it doesn't have to be an actual value in scope.
+///
+/// ```
+/// use valq::pointer_of;
+///
+/// assert_eq!(pointer_of!(obj.a.b[0]), "/a/b/0");
+/// assert_eq!(pointer_of!(obj."a/b"."m~n"), "/a~1b/m~0n");
+///
+/// // can be used in const context
+/// const PTR: &str = pointer_of!(config.servers[1].host);
+/// assert_eq!(PTR, "/servers/1/host");
+/// ```
+///
+/// Other indices are rejected at compile time, since they can't be rendered as an array index of JSON Pointer:
+///
+/// ```compile_fail
+/// let ptr = valq::pointer_of!(obj.arr[-1]);
+/// ```
+///
+/// ```compile_fail
+/// let ptr = valq::pointer_of!(obj.arr["x"]);
+/// ```
+///
+/// ```compile_fail
+/// let ptr = valq::pointer_of!(obj.arr[1_0usize]);
+/// ```
+///
+/// ```compile_fail
+/// let i = 0;
+/// let ptr = valq::pointer_of!(obj.arr[i]);
+/// ```
+#[macro_export]
+macro_rules! pointer_of {
+    /* collecting segments */
+    (@segs [$($seg:expr),*]) => {{
+        const SEGS: &[&str] = &[$($seg),*];
+        const LEN: usize = $crate::__private::json_pointer_len(SEGS);
+        const BUF: [u8; LEN] = $crate::__private::json_pointer::<LEN>(SEGS);
+        const PTR: &str = match ::core::str::from_utf8(&BUF) {
+            Ok(s) => s,
+            Err(_) => panic!("JSON Pointer built from valid strings must be valid UTF-8"),
+        };
+        PTR
+    }};
+    (@segs [$($seg:expr),*] . $key:ident $($rest:tt)*) => {
+        $crate::pointer_of!(@segs [$($seg,)* stringify!($key)] $($rest)*)
+    };
+    (@segs [$($seg:expr),*] . $key:literal $($rest:tt)*) => {
+        $crate::pointer_of!(@segs [$($seg,)* $key] $($rest)*)
+    };
+    (@segs [$($seg:expr),*] [ - $($_idx:tt)* ] $($rest:tt)*) => {
+        compile_error!("negative index is not allowed in pointer_of!()")
+    };
+    (@segs [$($seg:expr),*] [ $idx:literal ] $($rest:tt)*) => {
+        $crate::pointer_of!(@segs [$($seg,)* $crate::__private::pointer_index(stringify!($idx))] $($rest)*)
+    };
+    (@segs $($_:tt)*) => {
+        compile_error!("invalid query syntax for pointer_of!() (only keys and integer literal indices are allowed)")
+    };
+
+    /* entry point */
+    ($v:tt $($rest:tt)*) => {
+        $crate::pointer_of!(@segs [] $($rest)*)
+    };
+}
+
+/// A macro for finding the index of the first element of an array-like value that satisfies a predicate.
+///
+/// The first argument is a query in the same syntax as [`query_value`] (except for `mut` and `-> <to_type>`) that points to the array, and the second is a predicate (closure) taking a reference to each element.
+/// Results in `None` if the query fails, or no element satisfies the predicate.
+///
+/// Elements are enumerated by `get(idx)` from index `0` until it fails, so it works with any value type compatible with `query_value`.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::{position_of, query_value};
+///
+/// let obj = json!({"users": [{ "id": "abc" }, { "id": "def" }]});
+///
+/// assert_eq!(position_of!(obj.users, |u| query_value!(u.id -> str) == Some("def")), Some(1));
+/// assert_eq!(position_of!(obj.users, |u| query_value!(u.id -> str) == Some("xyz")), None);
+/// ```
+#[macro_export]
+macro_rules! position_of {
+    (@split [$($query:tt)+] , $pred:expr $(,)?) => {
+        match $crate::query_value!($($query)+) {
+            Some(arr) => (0..).map_while(|i: usize| arr.get(i)).position($pred),
+            None => None,
+        }
+    };
+    (@split [$($query:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::position_of!(@split [$($query)* $t] $($rest)*)
+    };
+    (@split $($_:tt)*) => {
+        compile_error!("invalid syntax for position_of!(): expected `position_of!(<query>, <predicate>)`")
+    };
+
+    /* entry point */
+    ($($args:tt)+) => {
+        $crate::position_of!(@split [] $($args)+)
+    };
+}
+
+/// A macro for taking a page (a range of elements) out of an array-like value, along with the total number of elements.
+///
+/// Takes a query in the same syntax as [`query_value`] (except for `mut` and `-> <to_type>`) that points to the array, followed by an offset and a limit (both `usize`).
+/// Results in a pair of references to the elements in the page and the total number of elements, or `None` if the query fails.
+/// The page is clamped to the bounds of the array, so an offset past the end just results in an empty page.
+///
+/// Like [`position_of`], elements are enumerated by `get(idx)`, so a value that is not an array is treated as an empty array.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::query_page;
+///
+/// let obj = json!({"items": ["a", "b", "c", "d", "e"]});
+///
+/// let (page, total) = query_page!(obj.items, 3, 10).unwrap();
+/// assert_eq!(page, vec![&json!("d"), &json!("e")]);
+/// assert_eq!(total, 5);
+///
+/// assert_eq!(query_page!(obj.items, 10, 10), Some((vec![], 5)));
+/// ```
+#[macro_export]
+macro_rules! query_page {
+    (@split [$($query:tt)+] , $offset:expr, $limit:expr $(,)?) => {
+        match $crate::query_value!($($query)+) {
+            Some(arr) => {
+                let total = (0..).map_while(|i: usize| arr.get(i)).count();
+                let offset: usize = ::std::cmp::min($offset, total);
+                let page: Vec<_> = (offset..total).take($limit).filter_map(|i| arr.get(i)).collect();
+                Some((page, total))
+            }
+            None => None,
+        }
+    };
+    (@split [$($query:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::query_page!(@split [$($query)* $t] $($rest)*)
+    };
+    (@split $($_:tt)*) => {
+        compile_error!("invalid syntax for query_page!(): expected `query_page!(<query>, <offset>, <limit>)`")
+    };
+
+    /* entry point */
+    ($($args:tt)+) => {
+        $crate::query_page!(@split [] $($args)+)
+    };
+}
+
+/// A macro for applying a mutation to every value matching a path, which can include wildcards.
+///
+/// The first argument is a path in the same syntax as [`query_value`] (except for `-> <to_type>` and slices; `mut` can be omitted), where `[*]` matches every element of an array.
+/// The second is a visitor closure, which is called with a mutable reference to each matched value.
+/// `return` (and `?` if it returns `Option`/`Result`) in it only ends the visit of the current value; the value returned from the visitor is ignored.
+/// Results in the number of visited values.
+///
+/// Elements are enumerated by `get_mut(idx)` from index `0` until it fails, so it works with any value type compatible with `query_value`.
+///
+/// `[? <query> == <expr>]` matches every child (element of an array, or value of an object entry) whose value at `<query>` equals to `<expr>`.
+/// Unlike other segments, it requires the value type to implement [`Editable`]. See also [`update_where`].
+///
+/// ```
+/// use serde_json::json;
+/// use valq::for_each_value_mut;
+///
+/// let mut obj = json!({"users": [{ "email": "Alice@Example.com" }, { "email": "BOB@example.com" }, { "name": "carol" }]});
+///
+/// let n = for_each_value_mut!(obj.users[*].email, |v| {
+///     if let Some(s) = v.as_str() {
+///         *v = json!(s.to_lowercase());
+///     }
+/// });
+/// assert_eq!(n, 2);
+/// assert_eq!(obj["users"][1]["email"], json!("bob@example.com"));
+/// ```
+#[macro_export]
+macro_rules! for_each_value_mut {
+    /* traversal */
+    (@trv [$name:literal $count:ident $visitor:expr] $cur:tt) => {
+        use $crate::__private::ReborrowMut as _;
+        $crate::__private::visit_mut($cur.reborrow_mut(), $visitor);
+        $count += 1;
+    };
+    (@trv [$($f:tt)+] $cur:tt . $key:ident $($rest:tt)*) => {
+        if let Some(v) = $cur.get_mut(stringify!($key)) {
+            $crate::for_each_value_mut! { @trv [$($f)+] v $($rest)* }
+        }
+    };
+    (@trv [$($f:tt)+] $cur:tt . $key:literal $($rest:tt)*) => {
+        if let Some(v) = $cur.get_mut($key as &str) {
+            $crate::for_each_value_mut! { @trv [$($f)+] v $($rest)* }
+        }
+    };
+    (@trv [$($f:tt)+] $cur:tt [*] $($rest:tt)*) => {
+        let mut i = 0usize;
+        while let Some(v) = $cur.get_mut(i) {
+            {
+                $crate::for_each_value_mut! { @trv [$($f)+] v $($rest)* }
+            }
+            i += 1;
+        }
+    };
+    (@trv [$($f:tt)+] $cur:tt [? $($pred:tt)+] $($rest:tt)*) => {
+        $crate::for_each_value_mut! { @pred [$($f)+] $cur [] [$($pred)+] $($rest)* }
+    };
+    (@trv [$($f:tt)+] $cur:tt [- $($n:tt)+] $($rest:tt)*) => {
+        if let Some(v) = $crate::query_value!(@neg_idx $cur, - $($n)+).and_then(|i| $cur.get_mut(i)) {
+            $crate::for_each_value_mut! { @trv [$($f)+] v $($rest)* }
+        }
+    };
+    (@trv [$($f:tt)+] $cur:tt [$field:ident = $val:expr] $($rest:tt)*) => {
+        if let Some(v) = $crate::query_value!(@field_pos $cur, stringify!($field), $val).and_then(|i| $cur.get_mut(i)) {
+            $crate::for_each_value_mut! { @trv [$($f)+] v $($rest)* }
+        }
+    };
+    (@trv [$($f:tt)+] $cur:tt [$field:literal = $val:expr] $($rest:tt)*) => {
+        if let Some(v) = $crate::query_value!(@field_pos $cur, $field, $val).and_then(|i| $cur.get_mut(i)) {
+            $crate::for_each_value_mut! { @trv [$($f)+] v $($rest)* }
+        }
+    };
+    (@trv [$name:literal $($f:tt)+] $cur:tt [.. $($hi:tt)*] $($rest:tt)*) => {
+        compile_error!(concat!("slice segment `[<range>]` is not supported in ", $name, "!(), use `[*]` instead"))
+    };
+    (@trv [$name:literal $($f:tt)+] $cur:tt [$lo:tt .. $($hi:tt)*] $($rest:tt)*) => {
+        compile_error!(concat!("slice segment `[<range>]` is not supported in ", $name, "!(), use `[*]` instead"))
+    };
+    (@trv [$name:literal $($f:tt)+] $cur:tt [..= $($hi:tt)*] $($rest:tt)*) => {
+        compile_error!(concat!("slice segment `[<range>]` is not supported in ", $name, "!(), use `[*]` instead"))
+    };
+    (@trv [$name:literal $($f:tt)+] $cur:tt [$lo:tt ..= $($hi:tt)*] $($rest:tt)*) => {
+        compile_error!(concat!("slice segment `[<range>]` is not supported in ", $name, "!(), use `[*]` instead"))
+    };
+    (@trv [$($f:tt)+] $cur:tt [$idx:expr] $($rest:tt)*) => {
+        if let Some(v) = $cur.get_mut($idx as usize) {
+            $crate::for_each_value_mut! { @trv [$($f)+] v $($rest)* }
+        }
+    };
+    (@trv [$($f:tt)+] $cur:tt ? $($rest:tt)*) => {
+        if !{
+            use $crate::__private::Reborrow as _;
+            $crate::Editable::is_null($cur.reborrow())
+        } {
+            $crate::for_each_value_mut! { @trv [$($f)+] $cur $($rest)* }
+        }
+    };
+    (@trv [$name:literal $($f:tt)+] $cur:tt .. $($rest:tt)*) => {
+        compile_error!(concat!("`..` is not supported in ", $name, "!()"))
+    };
+    (@trv [$name:literal $($f:tt)+] $($_:tt)*) => {
+        compile_error!(concat!("invalid query syntax for ", $name, "!()"))
+    };
+
+    /* filtering children by predicate `[? <query> == <expr>]` */
+    (@pred [$($f:tt)+] $cur:tt [] [== $val:expr] $($rest:tt)*) => {
+        $crate::for_each_value_mut! { @filter [$($f)+] $cur |e, want| *e == *want, $val; $($rest)* }
+    };
+    (@pred [$($f:tt)+] $cur:tt [$($q:tt)+] [== $val:expr] $($rest:tt)*) => {
+        $crate::for_each_value_mut! {
+            @filter [$($f)+] $cur
+            |e, want| $crate::query_value!(e $($q)+).is_some_and(|x| *x == *want), $val;
+            $($rest)*
+        }
+    };
+    (@pred [$($f:tt)+] $cur:tt [$($q:tt)*] [$t:tt $($p:tt)*] $($rest:tt)*) => {
+        $crate::for_each_value_mut! { @pred [$($f)+] $cur [$($q)* $t] [$($p)*] $($rest)* }
+    };
+    (@pred [$($f:tt)+] $cur:tt [$($q:tt)*] [] $($rest:tt)*) => {
+        compile_error!("predicate of `[? ...]` must be in the form of `<query> == <expr>`")
+    };
+    (@filter [$($f:tt)+] $cur:tt |$e:ident, $want:ident| $cond:expr, $val:expr; $($rest:tt)*) => {
+        use $crate::__private::ReborrowMut as _;
+        let $want = &$val;
+        $crate::Editable::for_each_child_mut($cur.reborrow_mut(), |v| {
+            let $e = &*v;
+            if $cond {
+                $crate::for_each_value_mut! { @trv [$($f)+] v $($rest)* }
+            }
+        });
+    };
+
+    (@split $name:literal [$v:tt $($path:tt)+] , $visitor:expr $(,)?) => {{
+        let mut count = 0usize;
+        {
+            $crate::for_each_value_mut! { @trv [$name count $visitor] $v $($path)+ }
+        }
+        count
+    }};
+    (@split $name:literal [$($query:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::for_each_value_mut!(@split $name [$($query)* $t] $($rest)*)
+    };
+    (@split $name:literal $($_:tt)*) => {
+        compile_error!(concat!("invalid syntax for ", $name, "!(): expected `", $name, "!(<query>, |v| ...)`"))
+    };
+
+    /* entry point */
+    // `$name` is the name of the macro called by the user (this or `update_where!()`), for error messages
+    (@named $name:literal mut $($args:tt)+) => {
+        $crate::for_each_value_mut!(@split $name [] $($args)+)
+    };
+    (@named $name:literal $($args:tt)+) => {
+        $crate::for_each_value_mut!(@split $name [] $($args)+)
+    };
+    ($($args:tt)+) => {
+        $crate::for_each_value_mut!(@named "for_each_value_mut" $($args)+)
+    };
+}
+
+/// A macro for updating every value matching a path with filters, like `UPDATE ... WHERE ...` of documents.
+///
+/// It is the same as [`for_each_value_mut`], except that it reads more naturally with `[? <query> == <expr>]` segments.
+/// Results in the number of updated values.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::update_where;
+///
+/// let mut obj = json!({"users": [
+///     { "name": "alice", "active": false },
+///     { "name": "bob", "active": true },
+///     { "name": "carol", "active": false },
+/// ]});
+///
+/// let n = update_where!(mut obj.users[? .active == false], |u| u["archived"] = json!(true));
+/// assert_eq!(n, 2);
+/// assert_eq!(obj["users"][2], json!({ "name": "carol", "active": false, "archived": true }));
+/// ```
+#[macro_export]
+macro_rules! update_where {
+    ($($args:tt)+) => {
+        $crate::for_each_value_mut!(@named "update_where" $($args)+)
+    };
+}
+
+/// A macro for querying multiple values under a single root at once.
+///
+/// The root value is written once, followed by `;` and comma-separated queries relative to the root (same syntax as [`query_value`], except for `mut`).
+/// Results in a tuple of results of each query if all of them succeeded, otherwise `None`.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::query_tuple;
+///
+/// let pkg = json!({"name": "valq", "version": "0.1.0", "authors": ["jiftechnify"]});
+///
+/// let (name, version, author) = query_tuple!(pkg; .name -> str, .version -> str, .authors[0] -> str).unwrap();
+/// assert_eq!((name, version, author), ("valq", "0.1.0", "jiftechnify"));
+///
+/// assert_eq!(query_tuple!(pkg; .name -> str, .license -> str), None);
+/// ```
+#[macro_export]
+macro_rules! query_tuple {
+    /* splitting queries */
+    (@split [$l:lifetime $root:ident] [$([$($done:tt)+])*] [$($cur:tt)+] , $($rest:tt)*) => {
+        $crate::query_tuple! { @split [$l $root] [$([$($done)+])* [$($cur)+]] [] $($rest)* }
+    };
+    (@split [$l:lifetime $root:ident] [$([$($done:tt)+])*] [$($cur:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::query_tuple! { @split [$l $root] [$([$($done)+])*] [$($cur)* $t] $($rest)* }
+    };
+    (@split [$l:lifetime $root:ident] [$([$($done:tt)+])*] [$($cur:tt)+]) => {
+        $crate::query_tuple! { @split [$l $root] [$([$($done)+])* [$($cur)+]] [] }
+    };
+    (@split [$l:lifetime $root:ident] [$([$($query:tt)+])+] []) => {
+        Some(($(
+            match $crate::query_value!($root $($query)+) {
+                Some(v) => v,
+                None => break $l None,
+            },
+        )+))
+    };
+
+    /* entry point */
+    ($root:expr; $($queries:tt)+) => {
+        'query: {
+            let root = &$root;
+            $crate::query_tuple! { @split ['query root] [] [] $($queries)+ }
+        }
+    };
+}
+
+/// A macro for querying a mandatory value, returning early from the enclosing function if the query fails.
+///
+/// Takes a query in the same syntax as [`query_value`], and results in the queried value itself (not wrapped in `Option`).
+/// If the query fails, it returns `None` from the enclosing function, or `Err(From::from(<err>))` if an error expression is given after `,` (the expression is evaluated only on failure).
+/// It works just like `?` on the result of `query_value`, but keeps handlers with many mandatory fields flat.
+///
+/// ```
+/// use serde_json::{json, Value};
+/// use valq::try_query;
+///
+/// fn user_email(req: &Value) -> Option<&str> {
+///     let email = try_query!(req.user.email -> str);
+///     Some(email)
+/// }
+///
+/// fn user_id(req: &Value) -> Result<u64, String> {
+///     let id = try_query!(req.user.id -> u64, "missing user id");
+///     Ok(id)
+/// }
+///
+/// let req = json!({"user": {"id": 42}});
+/// assert_eq!(user_email(&req), None);
+/// assert_eq!(user_id(&req), Ok(42));
+/// assert_eq!(user_id(&json!({})), Err("missing user id".to_string()));
+/// ```
+#[macro_export]
+macro_rules! try_query {
+    (@split [$($query:tt)+] , $err:expr $(,)?) => {
+        match $crate::query_value!($($query)+) {
+            Some(v) => v,
+            None => return ::core::result::Result::Err(::core::convert::From::from($err)),
+        }
+    };
+    (@split [$($query:tt)+]) => {
+        match $crate::query_value!($($query)+) {
+            Some(v) => v,
+            None => return None,
+        }
+    };
+    (@split [$($query:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::try_query!(@split [$($query)* $t] $($rest)*)
+    };
+
+    /* entry point */
+    ($($args:tt)+) => {
+        $crate::try_query!(@split [] $($args)+)
+    };
+}
+
+/// A macro for querying an optional value that must be of a certain type if present, telling "absent" apart from "present but wrong".
+///
+/// Takes a query in the same syntax as [`query_value`] that ends with a conversion (`-> <to_type>`), followed by an error expression after `,`.
+/// Results in `Ok(None)` if the value is missing or null, `Ok(Some(<converted>))` if the conversion succeeds, and `Err(<err>)` if the value is present but the conversion fails (the expression is evaluated only in that case).
+/// Checking null requires the value type to implement [`Editable`].
+///
+/// ```
+/// use serde_json::json;
+/// use valq::query_value_result;
+///
+/// let user = json!({"name": "alice", "middle_name": null, "age": "twenty"});
+///
+/// assert_eq!(query_value_result!(user.name -> str, "invalid name"), Ok(Some("alice")));
+/// assert_eq!(query_value_result!(user.middle_name -> str, "invalid middle name"), Ok(None));
+/// assert_eq!(query_value_result!(user.nickname -> str, "invalid nickname"), Ok(None));
+/// assert_eq!(query_value_result!(user.age -> u64, "invalid age"), Err("invalid age"));
+/// ```
+#[macro_export]
+macro_rules! query_value_result {
+    /* splitting arguments */
+    (@split [$($query:tt)+] -> $($rest:tt)+) => {
+        $crate::query_value_result!(@split_conv [$($query)+] [] $($rest)+)
+    };
+    (@split [$($query:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::query_value_result!(@split [$($query)* $t] $($rest)*)
+    };
+    (@split [$($query:tt)*]) => {
+        compile_error!("query_value_result!() needs a conversion `-> <to_type>` at the end of the query")
+    };
+    (@split_conv [$($query:tt)+] [$($conv:tt)+] , $err:expr $(,)?) => {
+        'query: {
+            use $crate::__private::Reborrow as _;
+            let Some(v) = $crate::query_value!($($query)+) else { break 'query Ok(None) };
+            if $crate::Editable::is_null(v.reborrow()) {
+                break 'query Ok(None);
+            }
+            #[allow(unused_labels)]
+            let converted = 'conv: { $crate::query_value! { @trv ['conv] v -> $($conv)+ } };
+            match converted {
+                Some(c) => Ok(Some(c)),
+                None => Err($err),
+            }
+        }
+    };
+    (@split_conv [$($query:tt)+] [$($conv:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::query_value_result!(@split_conv [$($query)+] [$($conv)* $t] $($rest)*)
+    };
+    (@split_conv [$($query:tt)+] [$($conv:tt)*]) => {
+        compile_error!("query_value_result!() needs an error expression after `,`")
+    };
+
+    /* entry point */
+    ($($args:tt)+) => {
+        $crate::query_value_result!(@split [] $($args)+)
+    };
+}
+
+/// Asserts that the number at a query is approximately equal to the expected one, for comparing floats in tests.
+///
+/// Takes a query in the same syntax as [`query_value`] that results in `f64` (typically ending with `-> f64`), followed by the expected value and `epsilon = <tolerance>`.
+/// Panics with the query, the expected and actual values and the delta between them if the delta exceeds the tolerance, or the query fails.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::assert_value_approx;
+///
+/// let obj = json!({"metrics": {"p99": 1.2300001}});
+///
+/// assert_value_approx!(obj.metrics.p99 -> f64, 1.23, epsilon = 1e-6);
+/// ```
+#[macro_export]
+macro_rules! assert_value_approx {
+    (@split [$($query:tt)+] , $expected:expr, epsilon = $eps:expr $(,)?) => {
+        match $crate::query_value!($($query)+) {
+            Some(actual) => {
+                let (actual, expected, eps): (f64, f64, f64) = (actual, $expected, $eps);
+                let delta = (actual - expected).abs();
+                if delta.is_nan() || delta > eps {
+                    panic!(
+                        "assertion failed: `{}` is approximately equal to {} (epsilon: {})\n  actual: {}\n   delta: {}",
+                        stringify!($($query)+), expected, eps, actual, delta,
+                    );
+                }
+            }
+            None => panic!(
+                "assertion failed: `{}` is approximately equal to {}, but the query failed",
+                stringify!($($query)+), $expected,
+            ),
+        }
+    };
+    (@split [$($query:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::assert_value_approx!(@split [$($query)* $t] $($rest)*)
+    };
+    (@split $($_:tt)*) => {
+        compile_error!("invalid syntax for assert_value_approx!(): expected `assert_value_approx!(<query>, <expected>, epsilon = <epsilon>)`")
+    };
+
+    /* entry point */
+    ($($args:tt)+) => {
+        $crate::assert_value_approx!(@split [] $($args)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    macro_rules! test_is_some_of_expected_val {
+        ($tests:expr) => {
+            for (res, exp) in $tests {
+                if let Some(act) = res {
+                    assert_eq!(act, &exp)
+                } else {
+                    panic!("expect Some(...) but actually None")
+                }
+            }
+        };
+    }
+
+    macro_rules! test_all_true_or_failed_idx {
+        ($test_res:expr) => {
+            if let Some(failed_idx) = $test_res.iter().position(|&r| !r) {
+                panic!("test idx: {} failed", failed_idx)
+            }
+        };
+    }
+
+    #[cfg(test)]
+    mod json {
+
+        use serde_json::{json, Value};
+
+        fn make_sample_json() -> Value {
+            // json!({"foo": { "bar": { "x": 1, "y": 2 }}})
+            json!({
+                "str": "s",
+                "nums": {
+                    "u64": 123,
+                    "i64": -123,
+                    "f64": 1.23,
+                },
+                "bool": true,
+                "null": null,
+                "obj": {
+                    "inner": "zzz"
+                },
+                "arr": [
+                    "first",
+                    42,
+                    { "hidden": "tale" },
+                    [0]
+                ],
+                "1st": "prop starts with digit!"
+            })
+        }
+
+        #[test]
+        fn test_query() {
+            let j = make_sample_json();
+
+            let tests = vec![
+                (query_value!(j.str), json!("s")),
+                (query_value!(j.nums.u64), json!(123)),
+                (query_value!(j.nums.i64), json!(-123)),
+                (query_value!(j.nums.f64), json!(1.23)),
+                (query_value!(j.bool), json!(true)),
+                (query_value!(j.null), json!(null)),
+                (query_value!(j.obj), json!({"inner": "zzz"})),
+                (
+                    query_value!(j.arr),
+                    json!(["first", 42, {"hidden": "tale"}, [0]]),
+                ),
+                (query_value!(j."1st"), json!("prop starts with digit!")),
+            ];
+
+            test_is_some_of_expected_val!(tests);
+        }
+
+        #[test]
+        fn test_indexing_array() {
+            let j = make_sample_json();
+
+            let tests = vec![
+                (query_value!(j.arr[0]), json!("first")),
+                (query_value!(j.arr[1]), json!(42)),
+                (query_value!(j.arr[2].hidden), json!("tale")), // more complex query!
+                (query_value!(j.arr[3][0]), json!(0)),          // successive indexing
+            ];
+
+            test_is_some_of_expected_val!(tests);
+        }
+
+        #[test]
+        fn test_indexing_by_expr() {
+            let j = make_sample_json();
+            let v = 1; // should not be confused with bindings inside the expansion
+
+            let tests = vec![
+                (query_value!(j.arr[v]), json!(42)),
+                (query_value!(j.arr[v + 1].hidden), json!("tale")),
+                (
+                    query_value!(j.arr[query_value!(j.arr[3][0] -> u64).unwrap()]),
+                    json!("first"),
+                ), // nested query
+            ];
+
+            test_is_some_of_expected_val!(tests);
+        }
+
+        #[test]
+        fn test_query_and_convert() {
+            let j = make_sample_json();
+
+            let tests = [
+                query_value!(j.str -> str) == Some("s"),
+                query_value!(j.nums.u64 -> u64) == Some(123),
+                query_value!(j.nums.i64 -> i64) == Some(-123),
+                query_value!(j.nums.f64 -> f64) == Some(1.23),
+                query_value!(j.bool -> bool) == Some(true),
+                query_value!(j.null -> null) == Some(()),
+                query_value!(j.obj -> object).unwrap().get("inner").unwrap() == "zzz",
+                query_value!(j.arr -> array).unwrap()
+                    == &vec![
+                        json!("first"),
+                        json!(42),
+                        json!({"hidden": "tale"}),
+                        json!([0]),
+                    ],
+            ];
+
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_query_and_convert_to_display() {
+            use std::borrow::Cow;
+
+            let j = make_sample_json();
+
+            let tests = [
+                query_value!(j.str -> display) == Some(Cow::Borrowed("s")),
+                query_value!(j.nums.u64 -> display) == Some(Cow::Owned("123".to_string())),
+                query_value!(j.nums.f64 -> display) == Some(Cow::Owned("1.23".to_string())),
+                query_value!(j.bool -> display) == Some(Cow::Owned("true".to_string())),
+                query_value!(j.null -> display) == Some(Cow::Owned("null".to_string())),
+                query_value!(j.obj -> display)
+                    == Some(Cow::Owned(r#"{"inner":"zzz"}"#.to_string())),
+                query_value!(j.unknown -> display).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            // strings should be borrowed, not copied
+            assert!(matches!(
+                query_value!(j.str -> display),
+                Some(Cow::Borrowed(_))
+            ));
+        }
+
+        #[test]
+        fn test_query_and_convert_user_defined() {
+            use crate::ValueAs;
+
+            #[derive(Debug, PartialEq)]
+            struct Semver(u64, u64, u64);
+
+            impl ValueAs<Semver> for Value {
+                fn value_as(&self) -> Option<Semver> {
+                    let mut parts = self.as_str()?.split('.').map(|p| p.parse().ok());
+                    let semver = Semver(parts.next()??, parts.next()??, parts.next()??);
+                    parts.next().is_none().then_some(semver)
+                }
+            }
+
+            let j = json!({"pkgs": [{"version": "1.2.3"}, {"version": "1.2"}, {"version": 1}]});
+
+            let tests = [
+                query_value!(j.pkgs[0].version -> Semver) == Some(Semver(1, 2, 3)),
+                query_value!(j.pkgs[1].version -> Semver).is_none(),
+                query_value!(j.pkgs[2].version -> Semver).is_none(),
+                query_value!(j.pkgs[3].version -> Semver).is_none(),
+            ];
+
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_query_and_convert_to_128bit_int() {
+            let j = json!({
+                "max_u64": u64::MAX,
+                "min_i64": i64::MIN,
+                "neg": -1,
+                "float": 1.5,
+            });
+
+            let tests = [
+                query_value!(j.max_u64 -> i128) == Some(u64::MAX as i128),
+                query_value!(j.max_u64 -> u128) == Some(u64::MAX as u128),
+                query_value!(j.min_i64 -> i128) == Some(i64::MIN as i128),
+                query_value!(j.min_i64 -> u128).is_none(),
+                query_value!(j.neg -> i128) == Some(-1),
+                query_value!(j.float -> i128).is_none(),
+                query_value!(j.max_u64 -> number).unwrap().to_string() == u64::MAX.to_string(),
+            ];
+
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_query_mut() {
+            let mut j = make_sample_json();
+
+            // rewriting value of prop
+            {
+                let obj_innner = query_value!(mut j.obj.inner).unwrap();
+                *obj_innner = json!("just woke up!");
+            }
+            assert_eq!(
+                query_value!(j.obj),
+                Some(&json!({"inner": "just woke up!"}))
+            );
+
+            // get inner object as Map, then add new prop via insert()
+            {
+                let obj = query_value!(mut j.obj -> object).unwrap();
+                obj.insert("new_prop".to_string(), json!("yeah"));
+            }
+            assert_eq!(query_value!(j.obj.new_prop -> str), Some("yeah"));
+
+            // get inner array as Vec, then append new value via push()
+            {
+                let arr = query_value!(mut j.arr -> array).unwrap();
+                arr.push(json!("appended!"));
+            }
+            assert_eq!(query_value!(j.arr[4] -> str), Some("appended!"));
+        }
+
+        #[test]
+        fn test_query_fail() {
+            let j = make_sample_json();
+
+            let tests = [
+                query_value!(j.unknown),   // non existent property
+                query_value!(j.nums.i128), // non existent property of nested object
+                query_value!(j.obj[0]),    // indexing against non-array value
+                query_value!(j.arr[100]),  // indexing out of bound
+            ]
+            .iter()
+            .map(|res| res.is_none())
+            .collect::<Vec<_>>();
+
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_query_fail_mut() {
+            let mut j = make_sample_json();
+
+            let tests = [
+                { query_value!(mut j.unknown).is_none() },
+                { query_value!(mut j.nums.i128).is_none() },
+                { query_value!(mut j.obj[0]).is_none() },
+                { query_value!(mut j.arr[100]).is_none() },
+            ];
+
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_query_wildcard() {
+            let j = json!({
+                "items": [
+                    { "name": "a", "tags": ["x", "y"], "price": 100 },
+                    { "name": "b", "tags": [], "price": "free" },
+                    { "tags": ["z"] },
+                ],
+                "matrix": [[1, 2], [3, 4]],
+            });
+
+            let tests = [
+                query_all!(j.items[*].name) == vec![&json!("a"), &json!("b")],
+                query_all!(j.items[*].name -> str) == vec!["a", "b"],
+                query_all!(j.items[*].price -> u64) == vec![100],
+                query_all!(j.items[*].tags[*] -> str) == vec!["x", "y", "z"],
+                query_all!(j.items[*].tags[0] -> str) == vec!["x", "z"],
+                query_all!(j.items[name = "b"].tags[*]).is_empty(),
+                query_all!(j.matrix[*][1] -> u64) == vec![2, 4],
+                query_all!(j.matrix[1][*] -> u64) == vec![3, 4],
+                query_all!(j.items[*].tags?[*] -> str).len() == 3,
+                query_all!(j.unknown[*]).is_empty(),
+                query_all!(j.items[0].name[*]).is_empty(), // not an array
+            ];
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_query_descent() {
+            let j = json!({
+                "id": 1,
+                "children": [
+                    { "id": 2, "children": [{ "id": 3 }] },
+                    { "name": "no id", "meta": { "id": "4" } },
+                ],
+                "owner": { "id": 5 },
+            });
+
+            let tests = [
+                query_all!(j..id -> u64) == vec![1, 2, 3, 5],
+                query_all!(j..id).len() == 5,
+                query_all!(j.children..id).len() == 3,
+                query_all!(j.children[0]..id -> u64) == vec![2, 3],
+                query_all!(j..children[0].id -> u64) == vec![2, 3],
+                query_all!(j..meta.id -> str) == vec!["4"],
+                query_all!(j..unknown).is_empty(),
+                query_all!(j.owner.id..id).is_empty(), // not a container
+            ];
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_query_negative_index() {
+            let mut j = make_sample_json();
+            let n = 2;
+
+            let tests = [
+                query_value!(j.arr[-1]) == Some(&json!([0])),
+                query_value!(j.arr[-n].hidden -> str) == Some("tale"),
+                query_value!(j.arr[-(n + 2)] -> str) == Some("first"),
+                query_value!(j.arr[-1][-1] -> u64) == Some(0),
+                query_value!(j.arr[-5]).is_none(), // out of range
+                query_value!(j.obj[-1]).is_none(), // not an array
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            *query_value!(mut j.arr[-1][-1]).unwrap() = json!(1);
+            assert_eq!(query_value!(j.arr[3][0] -> u64), Some(1));
+            assert!(query_value!(mut j.arr[-5]).is_none());
+
+            // the whole expression is the index, evaluated as a signed integer
+            let m: i64 = -1;
+            let tests = [
+                query_value!(j.arr[-n + 1]) == Some(&json!([1])),
+                query_value!(j.arr[-m] -> u64) == Some(42),
+                query_value!(j.arr[-(m + 5)] -> str) == Some("first"),
+            ];
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_selecting_segments_in_other_macros() {
+            use crate::{delete_value, insert_at};
+
+            let mut j = json!({
+                "users": [
+                    { "id": 1, "name": "alice", "profile": null },
+                    { "id": 2, "name": "bob", "profile": { "age": 20 } },
+                ],
+                "services": { "web": { "port": 80 }, "my api": { "port": 8080 } },
+            });
+            let id = 2;
+
+            // selected indices and keys are rendered in paths
+            let tests = [
+                query_value_with_path!(j.users[id = id].name -> str)
+                    == Some(("bob", ".users[1].name".to_string())),
+                query_value_with_path!(j.users[? .name == "alice"].id -> u64)
+                    == Some((1, ".users[0].id".to_string())),
+                query_value_with_path!(j.services[? .port == 8080])
+                    == Some((
+                        &json!({ "port": 8080 }),
+                        r#".services."my api""#.to_string(),
+                    )),
+                query_value_with_path!(j.users[0].profile?.age).is_none(),
+                query_value_with_path!(j.users[id = 3]).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            let (port, path) = query_value_with_path!(mut j.services[? .port == 80].port).unwrap();
+            *port = json!(8000);
+            assert_eq!(path, ".services.web.port");
+
+            let paths: Vec<_> = query_ancestors!(j.users["id" = 2].profile?.age)
+                .into_iter()
+                .map(|(p, _)| p)
+                .collect();
+            assert_eq!(
+                paths,
+                [
+                    "",
+                    ".users",
+                    ".users[1]",
+                    ".users[1].profile",
+                    ".users[1].profile.age"
+                ]
+            );
+            assert_eq!(query_ancestors!(j.users[0].profile?.age).len(), 4);
+
+            assert_eq!(
+                explain_query!(j.users[id = id].name),
+                ".users: found (array)\n.users[1]: found (object)\n.users[1].name: found (string)\n"
+            );
+            assert_eq!(
+                explain_query!(j.services[? .port == 1].port),
+                ".services: found (object)\n.services[? .port == 1]: not found (in object)\n.services[? .port == 1].port: skipped\n"
+            );
+
+            let n = for_each_value_mut!(j.users[*].profile?.age, |v| *v = json!(21));
+            assert_eq!(n, 1);
+            let n = for_each_value_mut!(j.users[id = 1].name, |v| *v = json!("alicia"));
+            assert_eq!(n, 1);
+            assert_eq!(query_value!(j.users[0].name -> str), Some("alicia"));
+
+            assert_eq!(
+                insert_at!(j.users[id = 2], json!({ "id": 3 })),
+                Some(&mut json!({ "id": 3 }))
+            );
+            assert_eq!(delete_value!(j.users[id = 3]), Some(json!({ "id": 3 })));
+            assert_eq!(
+                delete_value!(j.users[? .name == "bob"].profile),
+                Some(json!({ "age": 21 }))
+            );
+            assert_eq!(
+                delete_value!(j.services[? .port == 8000]),
+                Some(json!({ "port": 8000 }))
+            );
+            assert_eq!(delete_value!(j.users[? .name == "carol"]), None);
+            assert_eq!(
+                j,
+                json!({
+                    "users": [{ "id": 1, "name": "alicia", "profile": null }, { "id": 2, "name": "bob" }],
+                    "services": { "my api": { "port": 8080 } },
+                })
+            );
+        }
+
+        #[test]
+        fn test_negative_index_in_other_macros() {
+            use crate::{delete_value, insert_at};
+
+            let mut j = make_sample_json();
+            let m: i64 = -2;
+
+            let (v, path) = query_value_with_path!(j.arr[-1][-1]).unwrap();
+            assert_eq!((v, path.as_str()), (&json!(0), ".arr[3][0]"));
+            assert_eq!(
+                query_value_with_path!(j.arr[-m]),
+                Some((&json!({ "hidden": "tale" }), ".arr[2]".to_string()))
+            );
+            assert_eq!(query_value_with_path!(j.arr[-5]), None);
+
+            let paths: Vec<_> = query_ancestors!(j.arr[-2].hidden)
+                .into_iter()
+                .map(|(p, _)| p)
+                .collect();
+            assert_eq!(paths, ["", ".arr", ".arr[2]", ".arr[2].hidden"]);
+
+            assert_eq!(
+                explain_query!(j.arr[-1][-1]),
+                ".arr: found (array)\n.arr[3]: found (array)\n.arr[3][0]: found (number)\n"
+            );
+            assert_eq!(
+                explain_query!(j.obj[-1]),
+                ".obj: found (object)\n.obj[-1]: not found (expected array, found object)\n"
+            );
+
+            let n = for_each_value_mut!(j.arr[-1][-m + 1], |v| *v = json!(1));
+            assert_eq!(n, 0);
+            let n = for_each_value_mut!(j.arr[-1][-1], |v| *v = json!(1));
+            assert_eq!(n, 1);
+            assert_eq!(j["arr"][3], json!([1]));
+
+            assert_eq!(insert_at!(j.arr[-1], json!("x")), Some(&mut json!("x")));
+            assert_eq!(delete_value!(j.arr[-m]), Some(json!({ "hidden": "tale" })));
+            assert_eq!(delete_value!(j.arr[-5]), None);
+            assert_eq!(delete_value!(j.arr[-4]), Some(json!("first")));
+            assert_eq!(j["arr"], json!([42, "x", [1]]));
+        }
+
+        #[test]
+        fn test_query_slice() {
+            let mut j = json!({ "arr": [0, 1, 2, 3, 4], "obj": { "a": 1 } });
+            let (lo, hi) = (1, 3);
+
+            let tests = [
+                query_value!(j.arr[1..3]) == Some(&[json!(1), json!(2)][..]),
+                query_value!(j.arr[lo..hi]).map(<[_]>::len) == Some(2),
+                query_value!(j.arr[lo..=hi]).map(<[_]>::len) == Some(3),
+                query_value!(j.arr[..2]) == Some(&[json!(0), json!(1)][..]),
+                query_value!(j.arr[..=0]) == Some(&[json!(0)][..]),
+                query_value!(j.arr[3..]) == Some(&[json!(3), json!(4)][..]),
+                query_value!(j.arr[..]).map(<[_]>::len) == Some(5),
+                query_value!(j.arr[5..]).is_some_and(<[_]>::is_empty),
+                query_value!(j.arr[3..10]).is_none(), // out of range
+                query_value!(j.obj[0..1]).is_none(),  // not an array
+                query_value!(j.unknown[..]).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            for v in query_value!(mut j.arr[lo..hi]).unwrap() {
+                *v = json!(v.as_u64().unwrap() * 10);
+            }
+            assert_eq!(j["arr"], json!([0, 10, 20, 3, 4]));
+            assert!(query_value!(mut j.arr[..10]).is_none());
+        }
+
+        #[test]
+        fn test_query_null_as_missing() {
+            let mut j = json!({ "profile": null, "user": { "name": "alice", "nick": null } });
+
+            let tests = [
+                query_value!(j.profile?.name).is_none(),
+                query_value!(j.profile?).is_none(),
+                query_value!(j.user.nick?).is_none(),
+                query_value!(j.user?.name? -> str) == Some("alice"),
+                query_value!(j.profile) == Some(&Value::Null),
+                query_value_with_path!(j.user?.nick?).is_none(),
+                query_value_with_path!(j.user?.name -> str)
+                    == Some(("alice", ".user.name".to_string())),
+                explain_query!(j.profile?.name)
+                    == ".profile: found (null)\n.profile?: null\n.profile.name: skipped\n",
+                { query_value!(mut j.profile?).is_none() },
+                { query_value!(mut j.user?.name).is_some() },
+            ];
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_query_map_root() {
+            use std::collections::{BTreeMap, HashMap};
+
+            let j = make_sample_json();
+            let json_map = j.as_object().unwrap().clone();
+            let hash_map: HashMap<String, Value> = json_map.clone().into_iter().collect();
+            let btree_map: BTreeMap<String, Value> = json_map.clone().into_iter().collect();
+
+            let tests = [
+                query_value!(json_map.obj.inner -> str) == Some("zzz"),
+                query_value!(json_map.arr[2].hidden -> str) == Some("tale"),
+                query_value!(hash_map.obj.inner -> str) == Some("zzz"),
+                query_value!(hash_map."1st" -> str) == Some("prop starts with digit!"),
+                query_value!(btree_map.nums.u64 -> u64) == Some(123),
+                query_value!(btree_map.unknown).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            let mut hash_map = hash_map;
+            {
+                let inner = query_value!(mut hash_map.obj.inner).unwrap();
+                *inner = json!("modified");
+            }
+            assert_eq!(query_value!(hash_map.obj.inner -> str), Some("modified"));
+        }
+
+        #[test]
+        fn test_query_vec_root() {
+            let j = make_sample_json();
+            let rows = j["arr"].as_array().unwrap().clone();
+            let slice: &[Value] = &rows;
+
+            let tests = [
+                query_value!(rows[0] -> str) == Some("first"),
+                query_value!(rows[2].hidden -> str) == Some("tale"),
+                query_value!(slice[3][0] -> u64) == Some(0),
+                query_value!(slice[100]).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            let mut rows = rows;
+            {
+                let hidden = query_value!(mut rows[2].hidden).unwrap();
+                *hidden = json!("told");
+            }
+            assert_eq!(query_value!(rows[2].hidden -> str), Some("told"));
+        }
+
+        #[test]
+        fn test_query_owned() {
+            let j = make_sample_json();
+
+            let tests = [
+                query_value_owned!(j.obj) == Some(json!({"inner": "zzz"})),
+                query_value_owned!(j.arr[2].hidden) == Some(json!("tale")),
+                query_value_owned!(j.str -> str) == Some("s".to_string()),
+                query_value_owned!(j.nums.u64 -> u64) == Some(123),
+                query_value_owned!(j.arr -> array).map(|a| a.len()) == Some(4),
+                query_value_owned!(j.unknown).is_none(),
+                query_value_owned!(j.arr[? .hidden == "tale"].hidden -> display)
+                    == Some("tale".to_string()),
+            ];
+
+            test_all_true_or_failed_idx!(tests);
+
+            // results of `-> display` don't borrow the queried value
+            let (s, n): (Option<String>, Option<String>) = (
+                query_value_owned!(j.str -> display),
+                query_value_owned!(j.nums.u64 -> display),
+            );
+            drop(j);
+            assert_eq!((s.as_deref(), n.as_deref()), (Some("s"), Some("123")));
+        }
+
+        #[test]
+        fn test_query_with_path() {
+            let mut j = make_sample_json();
+            let i = 2;
+
+            let tests = [
+                query_value_with_path!(j.obj.inner)
+                    == Some((&json!("zzz"), ".obj.inner".to_string())),
+                query_value_with_path!(j.arr[i].hidden -> str)
+                    == Some(("tale", ".arr[2].hidden".to_string())),
+                query_value_with_path!(j."1st" -> str)
+                    == Some(("prop starts with digit!", r#"."1st""#.to_string())),
+                query_value_with_path!(j.arr[i].unknown).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            {
+                let (v, path) = query_value_with_path!(mut j.arr[3][0]).unwrap();
+                *v = json!(100);
+                assert_eq!(path, ".arr[3][0]");
+            }
+            assert_eq!(query_value!(j.arr[3][0] -> u64), Some(100));
+
+            {
+                let (arr, path) = query_value_with_path!(mut j.arr -> array).unwrap();
+                arr.clear();
+                assert_eq!(path, ".arr");
+            }
+            assert_eq!(query_value!(j.arr -> array), Some(&vec![]));
+        }
+
+        #[test]
+        fn test_query_ancestors() {
+            let j = make_sample_json();
+
+            assert_eq!(
+                query_ancestors!(j.arr[2].hidden),
+                vec![
+                    (String::new(), &j),
+                    (
+                        ".arr".to_string(),
+                        &json!(["first", 42, {"hidden": "tale"}, [0]])
+                    ),
+                    (".arr[2]".to_string(), &json!({"hidden": "tale"})),
+                    (".arr[2].hidden".to_string(), &json!("tale")),
+                ]
+            );
+            // stops at the last resolved segment
+            assert_eq!(
+                query_ancestors!(j.obj.unknown.inner),
+                vec![
+                    (String::new(), &j),
+                    (".obj".to_string(), &json!({"inner": "zzz"}))
+                ]
+            );
+            // the root is always included
+            assert_eq!(query_ancestors!(j.unknown), vec![(String::new(), &j)]);
+            let r = &j;
+            assert_eq!(query_ancestors!(r.obj).len(), 2);
+        }
+
+        #[test]
+        fn test_explain_query() {
+            let j = make_sample_json();
+
+            assert_eq!(
+                explain_query!(j.arr[2].hidden -> str),
+                ".arr: found (array)\n.arr[2]: found (object)\n.arr[2].hidden: found (string)\n-> str: converted\n"
+            );
+            assert_eq!(
+                explain_query!(j.obj.inner -> u64),
+                ".obj: found (object)\n.obj.inner: found (string)\n-> u64: conversion failed (found string)\n"
+            );
+            assert_eq!(
+                explain_query!(j.obj.unknown[0] -> str),
+                ".obj: found (object)\n.obj.unknown: not found (in object)\n.obj.unknown[0]: skipped\n-> str: skipped\n"
+            );
+            assert_eq!(
+                explain_query!(j.arr[9].x),
+                ".arr: found (array)\n.arr[9]: not found (in array of length 4)\n.arr[9].x: skipped\n"
+            );
+            assert_eq!(
+                explain_query!(j.obj[0]),
+                ".obj: found (object)\n.obj[0]: not found (expected array, found object)\n"
+            );
+            assert_eq!(
+                explain_query!(j.arr.x[-1]),
+                ".arr: found (array)\n.arr.x: not found (expected object, found array)\n.arr.x[-1]: skipped\n"
+            );
+        }
+
+        #[test]
+        fn test_pointer_of() {
+            let j = make_sample_json();
+
+            let tests = [
+                pointer_of!(j) == "",
+                pointer_of!(j.obj.inner) == "/obj/inner",
+                pointer_of!(j.arr[2].hidden) == "/arr/2/hidden",
+                pointer_of!(j.arr[10][0]) == "/arr/10/0",
+                pointer_of!(j."a/b"."~1") == "/a~1b/~01",
+                pointer_of!(j."") == "/",
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            // resolves to the same value as the query
+            assert_eq!(
+                j.pointer(pointer_of!(j.arr[2].hidden)),
+                query_value!(j.arr[2].hidden)
+            );
+        }
+
+        #[test]
+        fn test_runtime_query() {
+            use crate::Query;
+
+            let mut j = make_sample_json();
+
+            let tests = [
+                Query::parse("obj.inner").unwrap().get(&j) == Some(&json!("zzz")),
+                Query::parse(".arr[2].hidden").unwrap().get(&j) == Some(&json!("tale")),
+                Query::parse("[0]").unwrap().get(&json!([1])) == Some(&json!(1)),
+                Query::parse(r#"."1st""#).unwrap().get(&j).is_some(),
+                Query::parse("").unwrap().get(&j) == Some(&j),
+                Query::parse("obj.unknown").unwrap().get(&j).is_none(),
+                Query::parse("arr[100]").unwrap().get(&j).is_none(),
+                Query::parse("obj[0]").unwrap().get(&j).is_none(),
+                Query::parse("arr[-1][-1]").unwrap().get(&j) == Some(&json!(0)),
+                Query::parse("arr[-4]").unwrap().get(&j) == Some(&json!("first")),
+                Query::parse("arr[-5]").unwrap().get(&j).is_none(),
+                Query::parse("obj[-1]").unwrap().get(&j).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            let q: Query = "arr[3][0]".parse().unwrap();
+            *q.get_mut(&mut j).unwrap() = json!(1);
+            assert_eq!(query_value!(j.arr[3][0] -> u64), Some(1));
+
+            // parse errors
+            let errors = [
+                ("a..b", 2),
+                ("a[x]", 2),
+                ("a[+1]", 2),
+                ("a[-]", 2),
+                ("a[--1]", 2),
+                ("a[99999999999999999999]", 2),
+                ("a[0", 1),
+                ("a.\"b", 2),
+                ("a]", 1),
+                ("a[0]b", 4),
+            ];
+            for (q, pos) in errors {
+                assert_eq!(Query::parse(q).map_err(|e| e.position()), Err(pos), "{}", q);
+            }
+
+            // formatted queries can be parsed back
+            for q in ["", ".a.b[0]", ".a[-1]", r#"."1st"[2]"#, r#"."a.b\"c\\""#] {
+                assert_eq!(Query::parse(q).unwrap().to_string(), q);
+            }
+
+            // built queries are equivalent to parsed ones
+            let built = Query::new().key("arr").index(2).key("hidden");
+            assert_eq!(built, Query::parse("arr[2].hidden").unwrap());
+            assert_eq!(built.get(&j), Some(&json!("tale")));
+            assert_eq!(Query::new().key("a.b").to_string(), r#"."a.b""#);
+            assert_eq!(
+                Query::new().key("arr").index_from_end(2),
+                Query::parse("arr[-2]").unwrap()
+            );
+
+            let q: Query = "arr[-1][-1]".parse().unwrap();
+            *q.get_mut(&mut j).unwrap() = json!(2);
+            assert_eq!(query_value!(j.arr[3][0] -> u64), Some(2));
+        }
+
+        #[test]
+        fn test_position_of() {
+            let j = json!({
+                "users": [
+                    { "id": "abc", "active": false },
+                    { "id": "def", "active": true },
+                    { "id": "ghi", "active": true },
+                ],
+                "matrix": [[1, 2], [3, 4]],
+            });
+            let id = "ghi";
+
+            let tests = [
+                position_of!(j.users, |u| query_value!(u.active -> bool) == Some(true)) == Some(1),
+                position_of!(j.users, |u| query_value!(u.id -> str) == Some(id)) == Some(2),
+                position_of!(j.matrix[1], |n| n == 4) == Some(1),
+                position_of!(j.users, |u| query_value!(u.id -> str) == Some("xyz")).is_none(),
+                position_of!(j.unknown, |_| true).is_none(), // non existent property
+                position_of!(j.users[0], |_| true).is_none(), // not an array
+            ];
+
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_conv_method() {
+            let mut j = json!({
+                "name": "valq",
+                "tags": ["a", "b"],
+            });
+
+            let tests = [
+                query_value!(j.name -> as_str()) == Some("valq"),
+                query_value!(j.tags -> as_array()).map(Vec::len) == Some(2),
+                query_value!(j.tags -> as_str()).is_none(),
+                query_value!(j.unknown -> as_str()).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            query_value!(mut j.tags -> as_array_mut())
+                .unwrap()
+                .push(json!("c"));
+            assert_eq!(j["tags"], json!(["a", "b", "c"]));
+        }
+
+        #[test]
+        fn test_conv_narrow_int() {
+            let j = json!({
+                "port": 8080,
+                "big": 70000,
+                "neg": -1,
+                "float": 1.5,
+            });
+
+            let tests = [
+                query_value!(j.port -> u16) == Some(8080u16),
+                query_value!(j.port -> u32) == Some(8080u32),
+                query_value!(j.port -> usize) == Some(8080usize),
+                query_value!(j.port -> i16) == Some(8080i16),
+                query_value!(j.neg -> i8) == Some(-1i8),
+                query_value!(j.neg -> isize) == Some(-1isize),
+                query_value!(j.port -> u8).is_none(), // out of range
+                query_value!(j.big -> u16).is_none(), // out of range
+                query_value!(j.big -> i16).is_none(), // out of range
+                query_value!(j.neg -> u32).is_none(), // negative
+                query_value!(j.float -> i32).is_none(), // not an integer
+            ];
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_conv_enum() {
+            #[derive(Debug, PartialEq)]
+            enum Status {
+                Active,
+                Suspended,
+            }
+            impl std::str::FromStr for Status {
+                type Err = ();
+                fn from_str(s: &str) -> Result<Self, Self::Err> {
+                    match s {
+                        "active" => Ok(Status::Active),
+                        "suspended" => Ok(Status::Suspended),
+                        _ => Err(()),
+                    }
+                }
+            }
+
+            let j = json!({
+                "users": [
+                    { "status": "active" },
+                    { "status": "suspended" },
+                    { "status": "deleted" },
+                    { "status": 1 },
+                ]
+            });
+
+            let tests = [
+                query_value!(j.users[0].status -> enum(Status)) == Some(Status::Active),
+                query_value!(j.users[1].status -> enum(Status)) == Some(Status::Suspended),
+                query_value!(j.users[2].status -> enum(Status)).is_none(), // unknown variant
+                query_value!(j.users[3].status -> enum(Status)).is_none(), // not a string
+                query_value!(j.users[4].status -> enum(Status)).is_none(), // non existent
+                query_value!(j.users[0].status -> enum(std::net::IpAddr)).is_none(), // any `FromStr` works
+            ];
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_query_page() {
+            let j = json!({
+                "items": [0, 1, 2, 3, 4],
+                "empty": [],
+            });
+            let limit = 2;
+
+            let tests = [
+                query_page!(j.items, 0, limit) == Some((vec![&json!(0), &json!(1)], 5)),
+                query_page!(j.items, 4, limit) == Some((vec![&json!(4)], 5)),
+                query_page!(j.items, 5, limit) == Some((vec![], 5)),
+                query_page!(j.items, usize::MAX, usize::MAX) == Some((vec![], 5)),
+                query_page!(j.items, 1, 0) == Some((vec![], 5)),
+                query_page!(j.empty, 0, 10) == Some((vec![], 0)),
+                query_page!(j.unknown, 0, 10).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_for_each_value_mut() {
+            let mut j = json!({
+                "items": [
+                    { "price": 1.234, "tags": ["A", "B"] },
+                    { "price": 5.678, "tags": [] },
+                    { "tags": ["C"] },
+                ],
+                "matrix": [[1, 2], [3]],
+            });
+
+            let n = for_each_value_mut!(j.items[*].price, |v| {
+                *v = json!((v.as_f64().unwrap() * 100.0).round() / 100.0);
+            });
+            assert_eq!(n, 2);
+            assert_eq!(j["items"][0]["price"], json!(1.23));
+            assert_eq!(j["items"][1]["price"], json!(5.68));
+
+            let mut seen = Vec::new();
+            let n = for_each_value_mut!(mut j.items[*]."tags"[*], |v: &mut Value| {
+                seen.push(v.clone());
+                *v = json!(v.as_str().unwrap().to_lowercase());
+            });
+            assert_eq!(n, 3);
+            assert_eq!(seen, vec![json!("A"), json!("B"), json!("C")]);
+            assert_eq!(j["items"][0]["tags"], json!(["a", "b"]));
+
+            // `return` and `?` in the visitor only end the visit of the current value
+            let n = for_each_value_mut!(j.items[*].tags, |v| {
+                if v.as_array()?.is_empty() {
+                    return None;
+                }
+                v.as_array_mut()?.push(json!("z"));
+                Some(())
+            });
+            assert_eq!(n, 3);
+            assert_eq!(j["items"][1]["tags"], json!([]));
+            assert_eq!(j["items"][2]["tags"], json!(["c", "z"]));
+
+            let r = &mut j;
+            let tests = [
+                for_each_value_mut!(r.matrix[*][*], |v| *v = json!(0)) == 3,
+                for_each_value_mut!(r.matrix[0][*], |_| ()) == 2,
+                for_each_value_mut!(r.items[2].price, |_| ()) == 0,
+                for_each_value_mut!(r.unknown[*], |_| ()) == 0,
+            ];
+            test_all_true_or_failed_idx!(tests);
+            assert_eq!(j["matrix"], json!([[0, 0], [0]]));
+        }
+
+        #[test]
+        fn test_update_where() {
+            let mut j = json!({
+                "users": [
+                    { "name": "alice", "active": false, "roles": ["admin"] },
+                    { "name": "bob", "active": true, "roles": [] },
+                    { "name": "carol", "active": false, "roles": ["dev", "admin"] },
+                    { "name": "dave" },
+                ],
+                "services": {
+                    "web": { "port": 80 },
+                    "api": { "port": 8080 },
+                    "db": { "port": 80 },
+                },
+            });
+            let port = 80;
+
+            let n = update_where!(mut j.users[? .active == false].name, |v| {
+                *v = json!(v.as_str().unwrap().to_uppercase());
+            });
+            assert_eq!(n, 2);
+            assert_eq!(query_value!(j.users[0].name -> str), Some("ALICE"));
+            assert_eq!(query_value!(j.users[1].name -> str), Some("bob"));
+            assert_eq!(query_value!(j.users[3].name -> str), Some("dave"));
+
+            let tests = [
+                update_where!(j.services[? .port == port], |s| s["port"] = json!(8000)) == 2,
+                update_where!(j.users[*].roles[? == "admin"], |r| *r = json!("owner")) == 2,
+                update_where!(j.users[? .active == false][? .port == 80], |_| ()) == 0,
+                update_where!(j.unknown[? == 1], |_| ()) == 0,
+            ];
+            test_all_true_or_failed_idx!(tests);
+            assert_eq!(j["services"]["db"], json!({ "port": 8000 }));
+            assert_eq!(j["users"][2]["roles"], json!(["dev", "owner"]));
+
+            // the same children as in `query_value!`, but all of them
+            let mut users = json!([
+                { "name": "alice", "active": false },
+                { "name": "bob", "active": true },
+                { "name": "carol", "active": true },
+            ]);
+            let n = update_where!(users[? .active == true].name, |v| *v = json!("x"));
+            assert_eq!(n, 2);
+            assert_eq!(query_value!(users[? .name == "x"]), query_value!(users[1]));
+            assert_eq!(query_value!(users[0].name -> str), Some("alice"));
+        }
+
+        #[test]
+        fn test_find_entry() {
+            let mut j = json!({
+                "services": {
+                    "web": { "port": 80, "tls": { "enabled": false } },
+                    "api": { "port": 8080, "tls": { "enabled": true } },
+                    "db": { "port": 5432 },
+                },
+                "aliases": { "a": "x", "b": "y" },
+            });
+            let port = 5432;
+
+            let tests = [
+                query_value!(j.services[? .port == 8080].tls.enabled -> bool) == Some(true),
+                query_value!(j.services[? .tls.enabled == true].port -> u64) == Some(8080),
+                query_value!(j.services[? .port == port]) == Some(&json!({ "port": 5432 })),
+                query_value!(j.aliases[? == "y"] -> str) == Some("y"),
+                query_value!(j.services[? .port == 1]).is_none(), // no match
+                query_value!(j.aliases.a[? == "x"]).is_none(),    // not an object
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            *query_value!(mut j.services[? .port == 80].port).unwrap() = json!(8000);
+            assert_eq!(query_value!(j.services.web.port -> u64), Some(8000));
+
+            let r = &mut j;
+            assert!(query_value!(mut r.aliases[? == "x"]).is_some());
+
+            // elements of arrays are searched as well
+            let mut users = json!([
+                { "name": "alice", "active": false },
+                { "name": "bob", "active": true },
+                { "name": "carol", "active": true },
+            ]);
+            let tests = [
+                query_value!(users[? .active == true].name -> str) == Some("bob"),
+                query_value!(users[? .name == "carol"].active -> bool) == Some(true),
+                query_value!(users[1].name[? == "bob"]).is_none(), // not a container
+                query_value!(users[? .name == "dave"]).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            *query_value!(mut users[? .active == true].name).unwrap() = json!("bobby");
+            assert_eq!(query_value!(users[1].name -> str), Some("bobby"));
+        }
+
+        #[test]
+        fn test_find_by_field() {
+            let mut j = json!({
+                "users": [
+                    { "id": 1, "name": "alice", "email": "alice@example.com" },
+                    { "id": 2, "name": "bob", "email": "bob@example.com" },
+                    { "name": "anonymous" },
+                    { "id": 2, "name": "bob2" },
+                ],
+                "groups": [{ "members": [{ "name": "bob", "role": "admin" }] }],
+            });
+            let user_id = 2;
+            let name = String::from("bob");
+
+            let tests = [
+                query_value!(j.users[id = user_id].email -> str) == Some("bob@example.com"),
+                query_value!(j.users[id = 1].name -> str) == Some("alice"),
+                query_value!(j.users["name" = "anonymous"].id).is_none(),
+                query_value!(j.groups[0].members[name = name].role -> str) == Some("admin"),
+                query_value!(j.users[id = 3]).is_none(), // no match
+                query_value!(j.users[id = "2"]).is_none(), // type mismatch
+                query_value!(j.users[0][id = 1]).is_none(), // not an array
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            *query_value!(mut j.users[name = "bob"].email).unwrap() = json!("bob@example.org");
+            assert_eq!(
+                query_value!(j.users[1].email -> str),
+                Some("bob@example.org")
+            );
+        }
+
+        #[test]
+        fn test_query_tuple() {
+            let j = make_sample_json();
+
+            let tests = [
+                query_tuple!(j; .str -> str, .nums.u64 -> u64, .arr[2].hidden -> str)
+                    == Some(("s", 123, "tale")),
+                query_tuple!(j["obj"]; .inner) == Some((&json!("zzz"),)),
+                query_tuple!(j; .bool -> bool, ."1st" -> str,)
+                    == Some((true, "prop starts with digit!")),
+                query_tuple!(j; .str -> str, .unknown -> str).is_none(),
+                query_tuple!(j; .str -> u64, .bool -> bool).is_none(),
+            ];
+
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_compact_value() {
+            use crate::compact_value;
+
+            let mut j = json!({
+                "payload": {
+                    "a": null,
+                    "b": [1, null, { "c": null }],
+                    "d": { "e": null, "f": {} },
+                    "g": "keep",
+                },
+                "other": null,
+            });
+
+            assert!(compact_value!(j.payload).is_some());
+            assert_eq!(
+                j,
+                json!({
+                    "payload": {
+                        "b": [1, {}],
+                        "d": { "f": {} },
+                        "g": "keep",
+                    },
+                    "other": null, // outside of the path
+                })
+            );
+
+            assert!(compact_value!(mut j.payload, remove_empty).is_some());
+            assert_eq!(
+                j,
+                json!({
+                    "payload": {
+                        "b": [1],
+                        "g": "keep",
+                    },
+                    "other": null,
+                })
+            );
+
+            assert!(compact_value!(j.unknown).is_none());
+        }
+
+        #[test]
+        fn test_ensure_path() {
+            use crate::ensure_path;
+
+            let mut j = json!({
+                "a": { "b": 1 },
+                "arr": [null],
+                "n": null,
+            });
+
+            // creates missing containers
+            ensure_path!(j.x.y."z"[0]).unwrap();
+            assert_eq!(j["x"], json!({ "y": { "z": [{}] } }));
+
+            // replaces nulls, keeps existing values
+            ensure_path!(j.arr[0].c).unwrap();
+            ensure_path!(j.n[0]).unwrap();
+            ensure_path!(j.a).unwrap()["c"] = json!(2);
+            assert_eq!(j["arr"], json!([{ "c": {} }]));
+            assert_eq!(j["n"], json!([{}]));
+            assert_eq!(j["a"], json!({ "b": 1, "c": 2 }));
+
+            // works on `&mut Value` as well
+            let r = &mut j;
+            assert!(ensure_path!(mut r.arr[1]).is_some());
+            assert_eq!(j["arr"], json!([{ "c": {} }, {}]));
+
+            // type mismatches and out-of-range index
+            let orig = j.clone();
+            assert!(ensure_path!(j.a.b.c).is_none());
+            assert!(ensure_path!(j.a.b).is_none());
+            assert!(ensure_path!(j.a[0]).is_none());
+            assert!(ensure_path!(j.arr[3]).is_none());
+            assert_eq!(j, orig);
+        }
+
+        #[test]
+        fn test_set_value() {
+            use crate::set_value;
+
+            let mut j = make_sample_json();
+            let i = 3;
+
+            let tests = [
+                set_value!(mut j.obj.inner = json!("new")) == Some(json!("zzz")),
+                set_value!(j.arr[i][0] = json!(1)) == Some(json!(0)),
+                set_value!(j.arr[2]."hidden" = json!(null)) == Some(json!("tale")),
+                set_value!(j.obj.unknown = json!(1)).is_none(),
+                set_value!(j.arr[100] = json!(1)).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            assert_eq!(query_value!(j.obj.inner -> str), Some("new"));
+            assert_eq!(query_value!(j.arr[3][0] -> u64), Some(1));
+            assert!(query_value!(j.obj.unknown).is_none());
+        }
+
+        #[test]
+        fn test_upsert_value() {
+            use crate::upsert_value;
+
+            let mut j = json!({ "a": { "b": 1 }, "s": "str", "arr": [null] });
+
+            let tests = [
+                upsert_value!(mut j.a.b = json!(2)) == Some(&mut json!(2)),
+                upsert_value!(j.a.c.d = json!(3)) == Some(&mut json!(3)),
+                upsert_value!(j.x[0].y = json!(4)) == Some(&mut json!(4)),
+                upsert_value!(j.arr[0].z = json!(5)) == Some(&mut json!(5)),
+                upsert_value!(j.arr[1] = json!(6)) == Some(&mut json!(6)),
+                upsert_value!(j.s.t = json!(7)).is_none(), // not an object
+                upsert_value!(j.arr[5] = json!(8)).is_none(), // out of range
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            assert_eq!(
+                j,
+                json!({
+                    "a": { "b": 2, "c": { "d": 3 } },
+                    "s": "str",
+                    "arr": [{ "z": 5 }, 6],
+                    "x": [{ "y": 4 }],
+                })
+            );
+        }
+
+        #[test]
+        fn test_copy_value() {
+            use crate::copy_value;
+
+            let mut j = json!({
+                "defaults": { "limits": { "rps": 100 }, "plan": "free" },
+                "tenants": [{ "name": "a", "plan": "pro" }],
+            });
+            let t = 0;
+
+            assert!(copy_value!(mut j; .defaults.limits => .tenants[t].limits).is_some());
+            assert!(copy_value!(j; .defaults.plan => .tenants[t].plan).is_some()); // overwrites
+            assert!(copy_value!(j; .tenants[0] => .tenants[1]).is_some()); // pushes
+            assert_eq!(
+                j["tenants"],
+                json!([
+                    { "name": "a", "plan": "free", "limits": { "rps": 100 } },
+                    { "name": "a", "plan": "free", "limits": { "rps": 100 } },
+                ])
+            );
+
+            // copied values are independent of the source
+            *copy_value!(j; .defaults => ."backup"."defaults").unwrap() = json!(null);
+            assert_eq!(j["defaults"]["plan"], json!("free"));
+
+            let r = &mut j;
+            let orig = r.clone();
+            let tests = [
+                copy_value!(r; .unknown => .tenants[0].limits).is_none(), // no source
+                copy_value!(r; .defaults => .defaults.plan.x).is_none(),  // `plan` is not an object
+                copy_value!(r; .defaults => .tenants[3]).is_none(),       // out of range
+            ];
+            test_all_true_or_failed_idx!(tests);
+            assert_eq!(j, orig);
+        }
+
+        #[test]
+        fn test_migrate_value() {
+            use crate::migrate_value;
+
+            let mut j = json!({
+                "v1": { "name": "a", "ports": [80, 443], "with": 1 },
+                "s": "str",
+            });
+            let port = 0;
+
+            let matched = migrate_value!(mut j;
+                .v1.name => .v2.meta."name",
+                .v1.ports[port] => .v2.http_port with |v: Value| json!(v.as_u64().unwrap() + 8000),
+                .v1.with => .v2.with,
+                .v1.unknown => .v2.unknown,       // source doesn't exist
+                .v1.ports[0] => .s.port,          // destination can't be created
+            );
+            assert_eq!(matched, [true, true, true, false, false]);
+            assert_eq!(
+                j,
+                json!({
+                    "v1": { "ports": [443] },
+                    "v2": { "meta": { "name": "a" }, "http_port": 8080, "with": 1 },
+                    "s": "str",
+                })
+            );
+
+            // rules are applied in order
+            let mut j = json!({ "a": 1 });
+            assert_eq!(migrate_value!(j; .a => .b, .b => .c), [true, true]);
+            assert_eq!(j, json!({ "c": 1 }));
+
+            // paths sharing a prefix without overlapping
+            let mut j = json!({ "a": { "b": 1 } });
+            assert_eq!(migrate_value!(j; .a.b => .a.c, .a => .ab), [true, true]);
+            assert_eq!(j, json!({ "ab": { "c": 1 } }));
+
+            // moving a value out of its parent keeps the moved value intact (moving into the parent itself is rejected at compile time)
+            let mut j = json!({ "a": { "b": { "b": 1, "c": 2 } } });
+            assert_eq!(migrate_value!(j; .a.b => .b), [true]);
+            assert_eq!(j, json!({ "a": {}, "b": { "b": 1, "c": 2 } }));
+        }
+
+        #[test]
+        fn test_insert_at() {
+            use crate::insert_at;
+
+            let mut j = json!({
+                "steps": ["build", "deploy"],
+                "matrix": [[1, 3]],
+                "obj": {},
+            });
+
+            let tests = [
+                insert_at!(mut j.steps[1], json!("test")) == Some(&mut json!("test")),
+                insert_at!(j.steps[0], json!("lint")).is_some(), // head
+                insert_at!(j.steps[4], json!("notify")).is_some(), // tail
+                insert_at!(j.matrix[0][1], json!(2)).is_some(),
+                insert_at!(j.steps[6], json!(null)).is_none(), // out of range
+                insert_at!(j.obj[0], json!(null)).is_none(),   // not an array
+                insert_at!(j.unknown[0], json!(null)).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+            assert_eq!(
+                j["steps"],
+                json!(["lint", "build", "test", "deploy", "notify"])
+            );
+            assert_eq!(j["matrix"], json!([[1, 2, 3]]));
+
+            // root can be an array
+            let mut arr = json!([1, 3]);
+            let r = &mut arr;
+            assert!(insert_at!(r[1], json!(2)).is_some());
+            assert_eq!(arr, json!([1, 2, 3]));
+        }
+
+        #[test]
+        fn test_pop_value() {
+            use crate::pop_value;
+
+            let mut j = json!({
+                "queue": [1, 2],
+                "matrix": [[1], []],
+                "obj": { "a": 1 },
+            });
+
+            let tests = [
+                pop_value!(mut j.queue) == Some(json!(2)),
+                pop_value!(j.queue) == Some(json!(1)),
+                pop_value!(j.queue).is_none(), // empty
+                pop_value!(j.matrix[0]) == Some(json!(1)),
+                pop_value!(j.matrix[1]).is_none(), // empty
+                pop_value!(j.obj).is_none(),       // not an array
+                pop_value!(j.unknown).is_none(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+            assert_eq!(j["queue"], json!([]));
+        }
+
+        #[test]
+        fn test_delete_value() {
+            use crate::delete_value;
+
+            let mut j = make_sample_json();
+            let i = 2;
+
+            let tests = [
+                delete_value!(mut j.obj.inner) == Some(json!("zzz")),
+                delete_value!(j.arr[i].hidden) == Some(json!("tale")),
+                delete_value!(j.arr[0]) == Some(json!("first")),
+                delete_value!(j."1st").is_some(),
+                delete_value!(j.obj.inner).is_none(), // already removed
+                delete_value!(j.arr[100]).is_none(),  // out of range
+                delete_value!(j.str.x).is_none(),     // not an object
+                delete_value!(j.obj[0]).is_none(),    // not an array
+            ];
+            test_all_true_or_failed_idx!(tests);
+
+            assert_eq!(j["obj"], json!({}));
+            assert_eq!(j["arr"], json!([42, {}, [0]]));
+            assert!(query_value!(j."1st").is_none());
+        }
+
+        #[test]
+        fn test_try_query() {
+            fn name_and_age(j: &Value) -> Option<(&str, u64)> {
+                let name = try_query!(j.name -> str);
+                let age = try_query!(j.age -> u64);
+                Some((name, age))
             }
-        };
-    }
 
-    macro_rules! test_all_true_or_failed_idx {
-        ($test_res:expr) => {
-            if let Some(failed_idx) = $test_res.iter().position(|&r| !r) {
-                panic!("test idx: {} failed", failed_idx)
+            #[derive(Debug, PartialEq)]
+            enum Error {
+                Missing(&'static str),
+            }
+            fn first_tag(j: &mut Value) -> Result<&mut Value, Error> {
+                let tag = try_query!(mut j.tags[0], Error::Missing("tags"));
+                Ok(tag)
             }
-        };
-    }
 
-    #[cfg(test)]
-    mod json {
+            let mut j = json!({ "name": "alice", "age": 20, "tags": ["a"] });
 
-        use serde_json::{json, Value};
+            let tests = [
+                name_and_age(&j) == Some(("alice", 20)),
+                name_and_age(&json!({ "name": "bob" })).is_none(),
+                name_and_age(&json!({ "name": 1, "age": 20 })).is_none(),
+                first_tag(&mut json!({})) == Err(Error::Missing("tags")),
+            ];
+            test_all_true_or_failed_idx!(tests);
 
-        fn make_sample_json() -> Value {
-            // json!({"foo": { "bar": { "x": 1, "y": 2 }}})
-            json!({
-                "str": "s",
-                "nums": {
-                    "u64": 123,
-                    "i64": -123,
-                    "f64": 1.23,
-                },
-                "bool": true,
-                "null": null,
-                "obj": {
-                    "inner": "zzz"
-                },
-                "arr": [
-                    "first",
-                    42,
-                    { "hidden": "tale" },
-                    [0]
-                ],
-                "1st": "prop starts with digit!"
-            })
+            *first_tag(&mut j).unwrap() = json!("b");
+            assert_eq!(j["tags"], json!(["b"]));
         }
 
         #[test]
-        fn test_query() {
+        fn test_query_value_result() {
             let j = make_sample_json();
+            let n = json!({ "x": null });
 
-            let tests = vec![
-                (query_value!(j.str), json!("s")),
-                (query_value!(j.nums.u64), json!(123)),
-                (query_value!(j.nums.i64), json!(-123)),
-                (query_value!(j.nums.f64), json!(1.23)),
-                (query_value!(j.bool), json!(true)),
-                (query_value!(j.null), json!(null)),
-                (query_value!(j.obj), json!({"inner": "zzz"})),
-                (
-                    query_value!(j.arr),
-                    json!(["first", 42, {"hidden": "tale"}, [0]]),
-                ),
-                (query_value!(j."1st"), json!("prop starts with digit!")),
+            let tests = [
+                query_value_result!(j.str -> str, "err") == Ok(Some("s")),
+                query_value_result!(j.nums.u64 -> u8, "err") == Ok(Some(123)),
+                query_value_result!(j.arr[0] -> str, "err") == Ok(Some("first")),
+                query_value_result!(j.unknown -> str, "err") == Ok(None),
+                query_value_result!(n.x -> str, "err") == Ok(None),
+                query_value_result!(j.str -> u64, "err") == Err("err"),
+                query_value_result!(j.nums.i64 -> u64, "err") == Err("err"),
             ];
+            test_all_true_or_failed_idx!(tests);
 
-            test_is_some_of_expected_val!(tests);
+            // error expression is evaluated only if the conversion fails
+            let mut evaluated = false;
+            let res: Result<_, ()> = query_value_result!(j.str -> str, evaluated = true);
+            assert!(res.is_ok() && !evaluated);
         }
 
         #[test]
-        fn test_indexing_array() {
+        fn test_assert_value_approx() {
+            let j = json!({ "metrics": { "p50": 0.1, "p99": 1.2300001, "count": 3 } });
+            let eps = 1e-6;
+
+            assert_value_approx!(j.metrics.p99 -> f64, 1.23, epsilon = eps);
+            assert_value_approx!(j.metrics.p50 -> f64, 0.3 - 0.2, epsilon = 1e-12);
+            assert_value_approx!(j.metrics.count -> f64, 3.0, epsilon = 0.0);
+        }
+
+        #[test]
+        #[should_panic(expected = "delta")]
+        fn test_assert_value_approx_fails_on_delta() {
+            let j = json!({ "metrics": { "p99": 1.24 } });
+            assert_value_approx!(j.metrics.p99 -> f64, 1.23, epsilon = 1e-6);
+        }
+
+        #[test]
+        #[should_panic(expected = "the query failed")]
+        fn test_assert_value_approx_fails_on_missing() {
+            let j = json!({ "metrics": { "p99": "1.23" } });
+            assert_value_approx!(j.metrics.p99 -> f64, 1.23, epsilon = 1e-6);
+        }
+
+        #[test]
+        fn test_hash_value() {
+            use crate::hash_value;
+
+            let j = json!({ "a": 1, "b": [true, null, "x"], "c": { "d": 0.5 } });
+
+            // must be stable across releases
+            assert_eq!(hash_value(&j), 0x6007_e8c7_edcb_16b5);
+            assert_eq!(hash_value(&json!(1)), hash_value(&json!(1u8)));
+
+            let tests = [
+                // key order
+                hash_value(&json!({ "c": { "d": 0.5 }, "b": [true, null, "x"], "a": 1 }))
+                    == hash_value(&j),
+                // integers and floats are told apart
+                hash_value(&json!({ "a": 1.0, "b": [true, null, "x"], "c": { "d": 0.5 } }))
+                    != hash_value(&j),
+                hash_value(&json!(-1)) == hash_value(&json!(-1i8)),
+                hash_value(&json!(0.0)) == hash_value(&json!(-0.0)),
+                // array order matters
+                hash_value(&json!({ "a": 1, "b": [null, true, "x"], "c": { "d": 0.5 } }))
+                    != hash_value(&j),
+                hash_value(&json!("1")) != hash_value(&json!(1)),
+                hash_value(&json!({ "a": [] })) != hash_value(&json!({ "a": {} })),
+                hash_value(&json!([["a"], []])) != hash_value(&json!([[], ["a"]])),
+            ];
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_value_stats() {
+            use crate::{stats_at, value_stats};
+
             let j = make_sample_json();
+            let stats = value_stats(&j);
 
-            let tests = vec![
-                (query_value!(j.arr[0]), json!("first")),
-                (query_value!(j.arr[1]), json!(42)),
-                (query_value!(j.arr[2].hidden), json!("tale")), // more complex query!
-                (query_value!(j.arr[3][0]), json!(0)),          // successive indexing
+            let tests = [
+                value_stats(&json!(1)).depth == 0,
+                value_stats(&json!(1)).node_count == 1,
+                value_stats(&json!([])).depth == 1,
+                value_stats(&json!([])).max_array_len() == 0,
+                value_stats(&json!({})).max_array_len() == 0,
+                value_stats(&json!({"a": [[], [[1]]]})).depth == 4,
+                stats.node_count > stats_at!(j.arr).unwrap().node_count,
+                stats_at!(j.arr).unwrap().max_array_len() == 4,
+                stats_at!(j.arr).unwrap().array_lens
+                    == std::collections::BTreeMap::from([(1, 1), (4, 1)]),
+                // 2 values + key "abc" + string "xy"
+                value_stats(&json!({"abc": "xy"})).approx_size
+                    == 2 * std::mem::size_of::<Value>() + 3 + 2,
+                stats_at!(j.unknown).is_none(),
             ];
+            test_all_true_or_failed_idx!(tests);
+        }
 
-            test_is_some_of_expected_val!(tests);
+        #[test]
+        fn test_deeply_nested_value() {
+            use crate::{hash_value, value_stats};
+
+            // deep enough to overflow the stack of a test thread if traversed recursively
+            const DEPTH: usize = 100_000;
+            let mut v = json!(1);
+            for _ in 0..DEPTH {
+                v = Value::Array(vec![v]);
+            }
+
+            let stats = value_stats(&v);
+            assert_eq!(stats.depth, DEPTH);
+            assert_eq!(stats.node_count, DEPTH + 1);
+            assert_ne!(hash_value(&v), hash_value(&json!([1])));
+
+            // dropping deeply nested values recurses as well
+            while let Some(inner) = v.as_array_mut().and_then(|a| a.pop()) {
+                v = inner;
+            }
         }
 
         #[test]
-        fn test_query_and_convert() {
-            let j = make_sample_json();
+        fn test_value_eq_unordered() {
+            use crate::value_eq_unordered;
+
+            let a = json!({
+                "items": [
+                    { "id": 1, "tags": ["x", "y"], "at": "2024-01-01" },
+                    { "id": 2, "tags": [], "at": "2024-01-02" },
+                    { "id": 2, "tags": [], "at": "2024-01-02" },
+                ],
+                "total": 3,
+            });
+            let b = json!({
+                "total": 3,
+                "items": [
+                    { "tags": [], "id": 2, "at": "2025-01-01" },
+                    { "tags": ["y", "x"], "id": 1, "at": "2025-01-02" },
+                    { "tags": [], "id": 2, "at": "2025-01-03" },
+                ],
+            });
+            let c = json!({
+                "items": [
+                    { "id": 1, "tags": ["x", "y"] },
+                    { "id": 1, "tags": [] },
+                    { "id": 2, "tags": [] },
+                ],
+            });
 
             let tests = [
-                query_value!(j.str -> str) == Some("s"),
-                query_value!(j.nums.u64 -> u64) == Some(123),
-                query_value!(j.nums.i64 -> i64) == Some(-123),
-                query_value!(j.nums.f64 -> f64) == Some(1.23),
-                query_value!(j.bool -> bool) == Some(true),
-                query_value!(j.null -> null) == Some(()),
-                query_value!(j.obj -> object).unwrap().get("inner").unwrap() == "zzz",
-                query_value!(j.arr -> array).unwrap()
-                    == &vec![
-                        json!("first"),
-                        json!(42),
-                        json!({"hidden": "tale"}),
-                        json!([0]),
-                    ],
+                !value_eq_unordered!(a.items, b.items),
+                value_eq_unordered!(a.items, b.items, ignore = ["[*].at"]),
+                value_eq_unordered!(a.items[0].tags, b.items[1].tags),
+                value_eq_unordered!(a.total, b.total),
+                !value_eq_unordered!(a.items, c.items, ignore = ["[*].at"]), // multiplicity differs
+                !value_eq_unordered!(c.items[0], a.items[0], ignore = ["at"]), // paths start with `.`
+                value_eq_unordered!(c.items[0], a.items[0], ignore = [".at", ".unknown"]),
+                !value_eq_unordered!(a.items, a.total), // array vs number
+                !value_eq_unordered!(a.items, b.unknown),
             ];
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_watch_paths() {
+            use crate::watch_paths;
+
+            let old = json!({
+                "server": { "host": "localhost", "port": 80 },
+                "limits": { "rps": 100 },
+                "plugins": [{ "name": "a" }],
+            });
+            let mut new = old.clone();
+            new["server"]["port"] = json!(8080);
+            new["plugins"] = json!([]);
+            new["log"] = json!({ "level": "info" });
+
+            assert_eq!(
+                watch_paths!(old, new; .server.port, .server.host, .limits, .plugins[0].name, .log, .unknown),
+                vec![
+                    (".server.port", Some(&json!(80)), Some(&json!(8080))),
+                    (".plugins[0].name", Some(&json!("a")), None),
+                    (".log", None, Some(&json!({ "level": "info" }))),
+                ]
+            );
+            let same = old.clone();
+            assert!(watch_paths!(old, same; .server, .limits).is_empty());
+
+            // paths are rendered in the same way regardless of spacing in the query
+            let old = json!({ "my key": [1, { "x": null }] });
+            let new = json!({ "my key": [2, { "x": 1 }] });
+            let paths: Vec<_> =
+                watch_paths!(old, new; . "my key" [0], ."my key"[ -1 ].x, ."my key"[1] ?)
+                    .into_iter()
+                    .map(|(p, _, _)| p)
+                    .collect();
+            assert_eq!(
+                paths,
+                [r#"."my key"[0]"#, r#"."my key"[-1].x"#, r#"."my key"[1]?"#]
+            );
+        }
+
+        #[test]
+        fn test_value_matches() {
+            use crate::value_matches;
+
+            let j = json!({
+                "user": { "name": "alice", "age": 20, "balance": -1, "ok": true },
+                "tags": ["a", "b"],
+                "matrix": [[1, 2], []],
+                "n": null,
+                "weird-key": 1,
+            });
+            let name = "alice";
+            let r = &j;
 
+            let tests = [
+                value_matches!(j, { user: { name: "alice", age: _ }, tags: [..] }),
+                value_matches!(r, { user: { name: name, balance: -1, ok: true } }),
+                value_matches!(j.user, { age: 10 + 10 }),
+                value_matches!(j, { "weird-key": 1, n: _, }),
+                value_matches!(j.tags, ["a", "b"]),
+                value_matches!(j.tags, ["a", ..]),
+                value_matches!(j.matrix, [[1, ..], []]),
+                value_matches!(j, {}),
+                !value_matches!(j, { user: { name: "bob" } }),
+                !value_matches!(j, { user: { email: _ } }), // missing key
+                !value_matches!(j.tags, ["a"]),             // length differs
+                !value_matches!(j.tags, ["a", "b", "c", ..]),
+                !value_matches!(j.tags, {}),   // not an object
+                !value_matches!(j.user, [..]), // not an array
+                !value_matches!(j.unknown, _),
+            ];
             test_all_true_or_failed_idx!(tests);
         }
 
         #[test]
-        fn test_query_mut() {
-            let mut j = make_sample_json();
+        fn test_fixture_value() {
+            use crate::fixture_value;
 
-            // rewriting value of prop
-            {
-                let obj_innner = query_value!(mut j.obj.inner).unwrap();
-                *obj_innner = json!("just woke up!");
-            }
+            let f = fixture_value!(Value;
+                .user.name -> str,
+                .user.age -> u64,
+                .user."is-admin" -> bool,
+                .scores[0] -> f64,
+                .scores[1] -> i64,
+                .tags -> array,
+                .matrix[0][0] -> str,
+                .meta,
+            );
             assert_eq!(
-                query_value!(j.obj),
-                Some(&json!({"inner": "just woke up!"}))
+                f,
+                json!({
+                    "user": { "name": "", "age": 0, "is-admin": false },
+                    "scores": [0.0, 0],
+                    "tags": [],
+                    "matrix": [[""]],
+                    "meta": {},
+                })
             );
+            assert!(query_value!(f.user.age -> u64).is_some());
+        }
 
-            // get inner object as Map, then add new prop via insert()
-            {
-                let obj = query_value!(mut j.obj -> object).unwrap();
-                obj.insert("new_prop".to_string(), json!("yeah"));
-            }
-            assert_eq!(query_value!(j.obj.new_prop -> str), Some("yeah"));
+        #[test]
+        fn test_arb_value() {
+            use crate::arb_value;
+            use proptest::prelude::*;
 
-            // get inner array as Vec, then append new value via push()
-            {
-                let arr = query_value!(mut j.arr -> array).unwrap();
-                arr.push(json!("appended!"));
-            }
-            assert_eq!(query_value!(j.arr[4] -> str), Some("appended!"));
+            let strategy = arb_value!(Value;
+                .user.name -> str,
+                .user.age -> u64,
+                .user.score -> f64,
+                .tags[0] -> bool,
+                .meta -> object,
+            );
+            proptest!(|(v in strategy)| {
+                prop_assert!(query_value!(v.user.name -> str).is_some());
+                prop_assert!(query_value!(v.user.age -> u64).is_some());
+                prop_assert!(query_value!(v.user.score -> f64).is_some());
+                prop_assert!(query_value!(v.tags[0] -> bool).is_some());
+                prop_assert_eq!(query_value!(v.meta), Some(&json!({})));
+            });
+
+            let strategy = arb_value!(Value, extra;
+                .user.name -> str,
+                .tags[0] -> bool,
+                .meta -> object,
+            );
+            proptest!(|(v in strategy)| {
+                prop_assert!(query_value!(v.user.name -> str).is_some());
+                prop_assert!(query_value!(v.tags[0] -> bool).is_some());
+                prop_assert!(query_value!(v.meta -> object).is_some());
+            });
+
+            // extras go to containers in pre-order, never replacing existing entries
+            let mut v = json!({"a": "keep", "tags": []});
+            crate::__private::merge_extras(
+                &mut v,
+                vec![
+                    (0, "a".to_string(), json!(1)),
+                    (1, "x".to_string(), json!(true)),
+                    (2, "b".to_string(), json!(2)),
+                ],
+            );
+            assert_eq!(v, json!({"a": "keep", "tags": [true], "b": 2}));
         }
 
         #[test]
-        fn test_query_fail() {
-            let j = make_sample_json();
+        #[should_panic(expected = "conflicts")]
+        fn test_fixture_value_conflict() {
+            use crate::fixture_value;
+
+            fixture_value!(Value; .a -> str, .a.b -> str);
+        }
+
+        #[test]
+        fn test_query_layers() {
+            use crate::{query_layers, Layers};
+
+            let layers = Layers::new()
+                .with_layer("defaults", make_sample_json())
+                .with_layer("file", json!({"str": "from file", "obj": {"extra": 1}}))
+                .with_layer("env", json!({"nums": {"u64": 456}}));
 
             let tests = [
-                query_value!(j.unknown),   // non existent property
-                query_value!(j.nums.i128), // non existent property of nested object
-                query_value!(j.obj[0]),    // indexing against non-array value
-                query_value!(j.arr[100]),  // indexing out of bound
-            ]
-            .iter()
-            .map(|res| res.is_none())
-            .collect::<Vec<_>>();
+                query_layers!(layers.str -> str) == Some(("file", "from file")),
+                query_layers!(layers.nums.u64 -> u64) == Some(("env", 456)),
+                query_layers!(layers.nums.i64 -> i64) == Some(("defaults", -123)),
+                query_layers!(layers.obj.extra -> u64) == Some(("file", 1)),
+                query_layers!(layers.obj.inner -> str) == Some(("defaults", "zzz")),
+                query_layers!(layers.arr[0] -> str) == Some(("defaults", "first")),
+                query_layers!(layers.unknown).is_none(),
+            ];
 
             test_all_true_or_failed_idx!(tests);
         }
 
         #[test]
-        fn test_query_fail_mut() {
-            let mut j = make_sample_json();
+        fn test_upsert_layers() {
+            use crate::{query_layers, upsert_layers, Layers};
+
+            let defaults = json!({ "server": { "port": 80 }, "tags": ["a"] });
+            let mut layers = Layers::new()
+                .with_layer("defaults", defaults.clone())
+                .with_layer("overrides", json!({}))
+                .with_layer("env", json!({ "server": { "host": "env-host" } }));
 
             let tests = [
-                { query_value!(mut j.unknown).is_none() },
-                { query_value!(mut j.nums.i128).is_none() },
-                { query_value!(mut j.obj[0]).is_none() },
-                { query_value!(mut j.arr[100]).is_none() },
+                upsert_layers!(mut layers, "overrides"; .server.port = json!(8080)).is_some(),
+                upsert_layers!(layers, "overrides"; .server.host = json!("shadowed")).is_some(),
+                upsert_layers!(layers, "runtime"; .tags[0] = json!("b")).is_some(), // new layer
+                upsert_layers!(layers, "runtime"; .tags[0].x = json!(1)).is_none(), // `tags[0]` is a string
             ];
+            test_all_true_or_failed_idx!(tests);
 
+            let tests = [
+                query_layers!(layers.server.port -> u64) == Some(("overrides", 8080)),
+                query_layers!(layers.server.host -> str) == Some(("env", "env-host")),
+                query_layers!(layers.tags[0] -> str) == Some(("runtime", "b")),
+                layers.layer("defaults") == Some(&defaults),
+                layers.iter().next().map(|(n, _)| n) == Some("runtime"),
+            ];
             test_all_true_or_failed_idx!(tests);
         }
     }
 
     #[cfg(test)]
     mod yaml {
-        use super::query_value;
         use serde_yaml::{from_str, Mapping, Sequence, Value};
 
         fn make_sample_yaml() -> Value {
@@ -450,6 +3758,15 @@ mod tests {
             test_is_some_of_expected_val!(tests);
         }
 
+        #[test]
+        fn test_query_null_as_missing() {
+            let y: Value = from_str("profile: ~\nuser: { name: alice }").unwrap();
+
+            assert!(query_value!(y.profile?.name).is_none());
+            assert!(query_value!(y.profile?).is_none());
+            assert_eq!(query_value!(y.user?.name -> str), Some("alice"));
+        }
+
         #[test]
         fn test_query_and_convert() {
             let y = make_sample_yaml();
@@ -468,11 +3785,114 @@ mod tests {
 
             test_all_true_or_failed_idx!(tests);
         }
+
+        #[test]
+        fn test_hash_value() {
+            use crate::hash_value;
+
+            let y: Value = from_str("{c: {d: 0.5}, b: [true, null, x], a: 1}").unwrap();
+            let j = serde_json::json!({ "a": 1, "b": [true, null, "x"], "c": { "d": 0.5 } });
+            assert_eq!(hash_value(&y), hash_value(&j));
+        }
+
+        #[test]
+        fn test_compact_value() {
+            use crate::compact_value;
+
+            let mut y: Value = from_str(
+                r#"
+                seq:
+                  - ~
+                  - first
+                  - hidden: ~
+                map:
+                  a: ~
+                  b: {}
+                "#,
+            )
+            .unwrap();
+
+            compact_value!(y.seq);
+            compact_value!(y.map, remove_empty);
+
+            let expected: Value = from_str(
+                r#"
+                seq:
+                  - first
+                  - {}
+                map: {}
+                "#,
+            )
+            .unwrap();
+            assert_eq!(y, expected);
+        }
+
+        #[test]
+        fn test_ensure_path() {
+            use crate::ensure_path;
+
+            let mut y: Value = from_str("a: ~").unwrap();
+            ensure_path!(y.a.b[0]).unwrap();
+
+            let expected: Value = from_str(
+                r#"
+                a:
+                  b:
+                    - {}
+                "#,
+            )
+            .unwrap();
+            assert_eq!(y, expected);
+        }
+
+        #[test]
+        fn test_runtime_query() {
+            use crate::{Query, Queryable};
+
+            let mut y = make_sample_yaml();
+
+            let q = Query::parse("seq[2].hidden").unwrap();
+            assert_eq!(q.get(&y).and_then(Queryable::as_str), Some("tale"));
+            *q.get_mut(&mut y).unwrap() = Value::Bool(true);
+            assert_eq!(q.get(&y).and_then(Queryable::as_bool), Some(true));
+            assert_eq!(
+                Query::parse("num")
+                    .unwrap()
+                    .get(&y)
+                    .and_then(Queryable::as_u64),
+                Some(123)
+            );
+        }
+
+        #[test]
+        fn test_migrate_value() {
+            use crate::migrate_value;
+
+            let mut y: Value = from_str("old: { name: a }").unwrap();
+            assert_eq!(migrate_value!(mut y; .old.name => .new.name), [true]);
+            assert_eq!(y, from_str::<Value>("old: {}\nnew: { name: a }").unwrap());
+        }
+
+        #[test]
+        fn test_fixture_value() {
+            use crate::fixture_value;
+
+            let y = fixture_value!(Value; .name -> str, .items[0].count -> i64, .labels -> mapping);
+            let expected: Value = from_str(
+                r#"
+                name: ""
+                items:
+                  - count: 0
+                labels: {}
+                "#,
+            )
+            .unwrap();
+            assert_eq!(y, expected);
+        }
     }
 
     #[cfg(test)]
     mod toml {
-        use super::query_value;
         use toml::{
             from_str,
             value::{Array, Table},
@@ -561,9 +3981,107 @@ mod tests {
                         .map(|v| Value::String(v.to_string()))
                         .collect::<Vec<_>>(),
                 query_value!(t.arr_of_tables -> array).unwrap().len() == 3,
+                query_value!(t.str -> display).unwrap() == "s",
+                query_value!(t.int -> display).unwrap() == "123",
             ];
 
             test_all_true_or_failed_idx!(tests);
         }
+
+        #[test]
+        fn test_hash_value() {
+            use crate::hash_value;
+
+            let t: Value = from_str("a = 1\nb = [true, \"x\"]\nc = { d = 0.5 }").unwrap();
+            let j = serde_json::json!({ "a": 1, "b": [true, "x"], "c": { "d": 0.5 } });
+            assert_eq!(hash_value(&t), hash_value(&j));
+
+            let t: Value = from_str("a = 1.0\nb = [true, \"x\"]\nc = { d = 0.5 }").unwrap();
+            assert_ne!(hash_value(&t), hash_value(&j));
+        }
+
+        #[test]
+        fn test_runtime_query() {
+            use crate::{Query, Queryable};
+
+            let t = make_sample_toml();
+
+            let tests = [
+                Query::parse("str")
+                    .unwrap()
+                    .get(&t)
+                    .and_then(Queryable::as_str)
+                    == Some("s"),
+                Query::parse("int")
+                    .unwrap()
+                    .get(&t)
+                    .and_then(Queryable::as_u64)
+                    == Some(123),
+                Query::parse("float")
+                    .unwrap()
+                    .get(&t)
+                    .and_then(Queryable::as_f64)
+                    == Some(1.23),
+                Query::parse("arr[0]")
+                    .unwrap()
+                    .get(&t)
+                    .and_then(Queryable::as_str)
+                    == Some("first"),
+                Query::parse("table.first").unwrap().get(&t).is_some(),
+            ];
+            test_all_true_or_failed_idx!(tests);
+        }
+
+        #[test]
+        fn test_compact_value() {
+            use crate::compact_value;
+
+            let mut t = make_sample_toml();
+            query_value!(mut t.table -> table)
+                .unwrap()
+                .insert("empty".to_string(), Value::Array(vec![]));
+
+            compact_value!(t.table, remove_empty);
+            assert_eq!(query_value!(t.table), Some(&Value::Table(sample_table())));
+        }
+
+        #[test]
+        fn test_ensure_path() {
+            use crate::ensure_path;
+
+            let mut t = make_sample_toml();
+            ensure_path!(t.table.sub.arr[0])
+                .unwrap()
+                .as_table_mut()
+                .unwrap()
+                .insert("k".to_string(), Value::Integer(1));
+
+            assert_eq!(query_value!(t.table.sub.arr[0].k -> integer), Some(1));
+            assert!(ensure_path!(t.table.sub.arr[2]).is_none());
+        }
+
+        #[test]
+        fn test_fixture_value() {
+            use crate::fixture_value;
+
+            let t = fixture_value!(Value; .package.name -> str, .package.version -> integer, .dependencies -> table);
+            assert_eq!(query_value!(t.package.name -> str), Some(""));
+            assert_eq!(query_value!(t.package.version -> integer), Some(0));
+            assert_eq!(
+                query_value!(t.dependencies -> table),
+                Some(&toml::Table::new())
+            );
+        }
+
+        #[test]
+        fn test_arb_value() {
+            use crate::arb_value;
+            use proptest::prelude::*;
+
+            proptest!(|(t in arb_value!(Value; .package.version -> integer, .package.edition -> float))| {
+                prop_assert!(query_value!(t.package.version -> integer).is_some());
+                prop_assert!(query_value!(t.package.edition -> float).is_some());
+            });
+        }
     }
 }
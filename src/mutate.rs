@@ -0,0 +1,778 @@
+use crate::Editable;
+
+/// Removes nulls from the value recursively.
+/// If `remove_empty` is `true`, empty objects/arrays (including ones that become empty by the removal) are removed as well.
+///
+/// Note that the value itself is not removed even if it is null or empty. See also [`compact_value`](crate::compact_value).
+pub fn compact<V: Editable>(value: &mut V, remove_empty: bool) {
+    value.retain_children(|child| {
+        compact(child, remove_empty);
+        !(child.is_null() || (remove_empty && child.is_empty_container()))
+    });
+}
+
+/// A macro for removing nulls (and optionally, empty objects/arrays) recursively from the value at the query.
+///
+/// Takes a query in the same syntax as [`query_value`](crate::query_value) (except for `-> <to_type>`; `mut` can be omitted), optionally followed by `, remove_empty` to remove empty objects/arrays as well.
+/// Results in the mutable reference to the compacted value, or `None` if the query fails.
+///
+/// Available for data types that implement [`Editable`](crate::Editable).
+///
+/// ```
+/// use serde_json::json;
+/// use valq::compact_value;
+///
+/// let mut obj = json!({"payload": {"a": null, "b": [1, null], "c": {"d": null}}});
+///
+/// compact_value!(obj.payload);
+/// assert_eq!(obj, json!({"payload": {"b": [1], "c": {}}}));
+///
+/// compact_value!(obj.payload, remove_empty);
+/// assert_eq!(obj, json!({"payload": {"b": [1]}}));
+/// ```
+#[macro_export]
+macro_rules! compact_value {
+    (@split [$($query:tt)+] , remove_empty $(,)?) => {
+        $crate::query_value!(mut $($query)+).map(|v| {
+            $crate::compact(v, true);
+            v
+        })
+    };
+    (@split [$($query:tt)+]) => {
+        $crate::query_value!(mut $($query)+).map(|v| {
+            $crate::compact(v, false);
+            v
+        })
+    };
+    (@split [$($query:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::compact_value!(@split [$($query)* $t] $($rest)*)
+    };
+
+    /* entry point */
+    (mut $($args:tt)+) => {
+        $crate::compact_value!(@split [] $($args)+)
+    };
+    ($($args:tt)+) => {
+        $crate::compact_value!(@split [] $($args)+)
+    };
+}
+
+/// A macro for creating objects/arrays along the path if they don't exist yet.
+///
+/// Takes a path in the same syntax as [`query_value`](crate::query_value) made of `.<key>` and `[<idx>]` segments only (`mut` can be omitted), since the other segments select values that may not exist yet.
+/// A missing (or null) value at `.key` is created as an object, and one at `[idx]` as an array; the value at the end of the path is created as an empty object.
+/// `[idx]` must be an array index (`usize`); use `."key"` for object keys that aren't identifiers.
+/// An element of an array can be created only at the index just past the end of the array (i.e. pushed).
+///
+/// Results in the mutable reference to the value at the end of the path, or `None` if any value along the path has an incompatible type (e.g. a string where an object is expected), or an index is out of range.
+/// Existing values are never overwritten, except for nulls.
+///
+/// Available for data types that implement [`Editable`](crate::Editable).
+///
+/// ```
+/// use serde_json::json;
+/// use valq::ensure_path;
+///
+/// let mut obj = json!({"server": {"host": "localhost"}});
+///
+/// let log = ensure_path!(obj.server.log.targets[0]).unwrap();
+/// log["file"] = json!("/var/log/app.log");
+/// assert_eq!(
+///     obj,
+///     json!({"server": {"host": "localhost", "log": {"targets": [{"file": "/var/log/app.log"}]}}}),
+/// );
+///
+/// // `host` is not an object
+/// assert!(ensure_path!(obj.server.host.name).is_none());
+/// // index out of range
+/// assert!(ensure_path!(obj.server.log.targets[2]).is_none());
+/// ```
+#[macro_export]
+macro_rules! ensure_path {
+    /* creating a value for the next segment */
+    (@new) => {
+        $crate::Editable::new_object()
+    };
+    (@new . $($_rest:tt)*) => {
+        $crate::Editable::new_object()
+    };
+    (@new [$($_idx:tt)*] $($_rest:tt)*) => {
+        $crate::Editable::new_array()
+    };
+
+    /* traversal */
+    // `[$l]` for ensuring the path, `[$l $val]` for setting `$val` at the end of the path
+    (@trv [$l:lifetime] $cur:ident) => {
+        if $crate::Editable::is_object($cur) || $crate::Editable::is_array($cur) {
+            Some($cur)
+        } else {
+            None
+        }
+    };
+    (@trv [$l:lifetime $val:expr] $cur:ident . $key:ident) => {
+        $crate::ensure_path! { @set_key [$l $val] $cur stringify!($key) }
+    };
+    (@trv [$l:lifetime $val:expr] $cur:ident . $key:literal) => {
+        $crate::ensure_path! { @set_key [$l $val] $cur $key }
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident [- $($n:tt)+] $($rest:tt)*) => {
+        compile_error!("negative index `[-<idx>]` is not supported in paths to be created")
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident [*] $($rest:tt)*) => {
+        compile_error!("only `.<key>` and `[<idx>]` segments are allowed in paths to be created")
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident [? $($pred:tt)*] $($rest:tt)*) => {
+        compile_error!("only `.<key>` and `[<idx>]` segments are allowed in paths to be created")
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident [$field:tt = $($v:tt)*] $($rest:tt)*) => {
+        compile_error!("only `.<key>` and `[<idx>]` segments are allowed in paths to be created")
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident [.. $($hi:tt)*] $($rest:tt)*) => {
+        compile_error!("only `.<key>` and `[<idx>]` segments are allowed in paths to be created")
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident [$lo:tt .. $($hi:tt)*] $($rest:tt)*) => {
+        compile_error!("only `.<key>` and `[<idx>]` segments are allowed in paths to be created")
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident [..= $($hi:tt)*] $($rest:tt)*) => {
+        compile_error!("only `.<key>` and `[<idx>]` segments are allowed in paths to be created")
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident [$lo:tt ..= $($hi:tt)*] $($rest:tt)*) => {
+        compile_error!("only `.<key>` and `[<idx>]` segments are allowed in paths to be created")
+    };
+    (@trv [$l:lifetime $val:expr] $cur:ident [$idx:expr]) => {
+        let i = $idx as usize;
+        if $crate::Editable::is_null($cur) {
+            *$cur = $crate::Editable::new_array();
+        }
+        let Some(elems) = $crate::Editable::elements_mut($cur) else { break $l None };
+        if i == elems.len() {
+            elems.push($val);
+        } else if i < elems.len() {
+            elems[i] = $val;
+        }
+        elems.get_mut(i)
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident . $key:ident $($rest:tt)*) => {
+        $crate::ensure_path! { @key [$l $($val)?] $cur stringify!($key), $($rest)* }
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident . $key:literal $($rest:tt)*) => {
+        $crate::ensure_path! { @key [$l $($val)?] $cur $key, $($rest)* }
+    };
+    (@trv [$l:lifetime $($val:tt)?] $cur:ident [$idx:expr] $($rest:tt)*) => {
+        let i = $idx as usize;
+        if $crate::Editable::is_null($cur) {
+            *$cur = $crate::Editable::new_array();
+        }
+        let Some(elems) = $crate::Editable::elements_mut($cur) else { break $l None };
+        if i == elems.len() {
+            elems.push($crate::ensure_path!(@new $($rest)*));
+        } else if i < elems.len() && $crate::Editable::is_null(&elems[i]) {
+            elems[i] = $crate::ensure_path!(@new $($rest)*);
+        }
+        let Some(v) = elems.get_mut(i) else { break $l None };
+        $crate::ensure_path! { @trv [$l $($val)?] v $($rest)* }
+    };
+    (@key [$l:lifetime $($val:tt)?] $cur:ident $key:expr, $($rest:tt)*) => {
+        let key: &str = $key;
+        if $crate::Editable::is_null($cur) {
+            *$cur = $crate::Editable::new_object();
+        }
+        if !$crate::Editable::is_object($cur) {
+            break $l None;
+        }
+        if $cur.get(key).map_or(true, $crate::Editable::is_null) {
+            $crate::Editable::insert_entry($cur, key, $crate::ensure_path!(@new $($rest)*));
+        }
+        let Some(v) = $cur.get_mut(key) else { break $l None };
+        $crate::ensure_path! { @trv [$l $($val)?] v $($rest)* }
+    };
+    (@set_key [$l:lifetime $val:expr] $cur:ident $key:expr) => {
+        let key: &str = $key;
+        if $crate::Editable::is_null($cur) {
+            *$cur = $crate::Editable::new_object();
+        }
+        if !$crate::Editable::is_object($cur) {
+            break $l None;
+        }
+        $crate::Editable::insert_entry($cur, key, $val);
+        $cur.get_mut(key)
+    };
+
+    /* setting a value at the end of the path, used by other macros */
+    (@set $v:tt = $val:expr; $($path:tt)+) => {
+        'query: {
+            use $crate::__private::ReborrowMut as _;
+            let cur = $v.reborrow_mut();
+            $crate::ensure_path! { @trv ['query $val] cur $($path)+ }
+        }
+    };
+
+    /* entry point */
+    (mut $($path:tt)+) => {
+        $crate::ensure_path!($($path)+)
+    };
+    ($v:tt $($path:tt)+) => {
+        'query: {
+            use $crate::__private::ReborrowMut as _;
+            let cur = $v.reborrow_mut();
+            $crate::ensure_path! { @trv ['query] cur $($path)+ }
+        }
+    };
+}
+
+/// A macro for assigning a value at a path, returning the value previously there.
+///
+/// Takes a query in the same syntax as [`query_value`](crate::query_value) (except for `-> <to_type>`; `mut` can be omitted), followed by `=` and the value to assign.
+/// Results in the old value at the path, or `None` if the query fails. Unlike [`ensure_path`](crate::ensure_path), nothing is created along the path, and the value to assign is evaluated only if the path exists.
+///
+/// Works with any data type that `query_value` supports for `mut` queries.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::set_value;
+///
+/// let mut obj = json!({"a": {"b": [1, 2]}});
+///
+/// assert_eq!(set_value!(mut obj.a.b[0] = json!(42)), Some(json!(1)));
+/// assert_eq!(obj, json!({"a": {"b": [42, 2]}}));
+///
+/// // the path must exist
+/// assert_eq!(set_value!(mut obj.a.c = json!(42)), None);
+/// ```
+#[macro_export]
+macro_rules! set_value {
+    (@split [$($query:tt)+] = $val:expr) => {
+        match $crate::query_value!(mut $($query)+) {
+            Some(target) => Some(::std::mem::replace(target, $val)),
+            None => None,
+        }
+    };
+    (@split [$($query:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::set_value!(@split [$($query)* $t] $($rest)*)
+    };
+    (@split [$($query:tt)*]) => {
+        compile_error!("set_value!() needs a value to assign after `=`")
+    };
+
+    /* entry point */
+    (mut $($args:tt)+) => {
+        $crate::set_value!(@split [] $($args)+)
+    };
+    ($($args:tt)+) => {
+        $crate::set_value!(@split [] $($args)+)
+    };
+}
+
+/// A macro for assigning a value at a path, creating objects/arrays along the path if they don't exist yet (like lodash's `_.set`).
+///
+/// Takes a path in the same syntax as paths of [`ensure_path`](crate::ensure_path) (`mut` can be omitted), followed by `=` and the value to assign.
+/// Objects/arrays along the path are created like `ensure_path` does, and a value already at the end of the path is overwritten.
+/// Results in the mutable reference to the assigned value, or `None` if the path can't be created (see `ensure_path` for the conditions).
+/// Use [`set_value`](crate::set_value) instead to assign only when the path already exists.
+///
+/// Available for data types that implement [`Editable`](crate::Editable).
+///
+/// ```
+/// use serde_json::json;
+/// use valq::upsert_value;
+///
+/// let mut config = json!({});
+///
+/// upsert_value!(mut config.server.port = json!(8080));
+/// upsert_value!(mut config.server.tls.cert = json!("cert.pem"));
+/// upsert_value!(mut config.plugins[0] = json!("auth"));
+/// assert_eq!(
+///     config,
+///     json!({"server": {"port": 8080, "tls": {"cert": "cert.pem"}}, "plugins": ["auth"]}),
+/// );
+/// ```
+#[macro_export]
+macro_rules! upsert_value {
+    (@split [$v:tt $($path:tt)+] = $val:expr) => {
+        $crate::ensure_path!(@set $v = $val; $($path)+)
+    };
+    (@split [$($query:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::upsert_value!(@split [$($query)* $t] $($rest)*)
+    };
+    (@split [$($query:tt)*]) => {
+        compile_error!("upsert_value!() needs a value to assign after `=`")
+    };
+
+    /* entry point */
+    (mut $($args:tt)+) => {
+        $crate::upsert_value!(@split [] $($args)+)
+    };
+    ($($args:tt)+) => {
+        $crate::upsert_value!(@split [] $($args)+)
+    };
+}
+
+/// A macro for copying the value at a path to another path in the same value.
+///
+/// Takes the root value, followed by `;` and a pair of paths (in the same syntax as paths of [`ensure_path`](crate::ensure_path)) separated by `=>`; `mut` before the root can be omitted.
+/// The value at the source path is cloned and set at the destination path, creating objects/arrays along the destination path like `ensure_path` does.
+/// A value already at the destination is overwritten.
+///
+/// Results in the mutable reference to the copied value, or `None` if the source path doesn't exist or the destination path can't be created.
+///
+/// Available for data types that implement [`Editable`](crate::Editable) and `Clone`.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::copy_value;
+///
+/// let mut obj = json!({"defaults": {"limits": {"rps": 100}}, "tenants": [{"name": "a"}]});
+///
+/// copy_value!(mut obj; .defaults.limits => .tenants[0].limits);
+/// assert_eq!(obj["tenants"][0], json!({"name": "a", "limits": {"rps": 100}}));
+/// ```
+#[macro_export]
+macro_rules! copy_value {
+    (@split $v:tt [$($src:tt)+] => $($dst:tt)+) => {
+        'copy: {
+            let Some(val) = $crate::query_value!($v $($src)+).cloned() else { break 'copy None };
+            $crate::ensure_path!(@set $v = val; $($dst)+)
+        }
+    };
+    (@split $v:tt [$($src:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::copy_value!(@split $v [$($src)* $t] $($rest)*)
+    };
+    (@split $($_:tt)*) => {
+        compile_error!("invalid syntax for copy_value!(): expected `copy_value!(<value>; <src path> => <dst path>)`")
+    };
+
+    /* entry point */
+    (mut $v:tt; $($paths:tt)+) => {
+        $crate::copy_value!(@split $v [] $($paths)+)
+    };
+    ($v:tt; $($paths:tt)+) => {
+        $crate::copy_value!(@split $v [] $($paths)+)
+    };
+}
+
+/// A macro for migrating a value by moving values between paths, driven by a list of rules.
+///
+/// Takes the root value, followed by `;` and comma-separated rules of the form `<src path> => <dst path>` (paths in the same syntax as paths of [`ensure_path`](crate::ensure_path)),
+/// optionally followed by `with <fn>` to transform the value on the way (`<fn>` is called with the owned value and must return the new value). `mut` before the root can be omitted.
+/// Rules are applied in order: for each rule, the value at the source path is moved to the destination path, creating objects/arrays along the destination path like `ensure_path` does.
+///
+/// Results in an array of `bool`s telling which rules matched, i.e. the source path existed and the value was moved.
+/// A rule doesn't match (and changes nothing) if the source path doesn't exist or the destination path can't be created.
+///
+/// Available for data types that implement [`Editable`](crate::Editable) and `Clone`.
+///
+/// ```
+/// use serde_json::{json, Value};
+/// use valq::migrate_value;
+///
+/// let mut doc = json!({"user_name": "alice", "age": "20", "legacy": {"tags": ["a"]}});
+///
+/// let matched = migrate_value!(mut doc;
+///     .user_name => .user.name,
+///     .age => .user.age with |v: Value| json!(v.as_str().and_then(|s| s.parse::<u64>().ok())),
+///     .legacy.tags => .user.tags,
+///     .nickname => .user.nickname,
+/// );
+/// assert_eq!(matched, [true, true, true, false]);
+/// assert_eq!(doc, json!({"user": {"name": "alice", "age": 20, "tags": ["a"]}, "legacy": {}}));
+/// ```
+///
+/// The destination path must not be the same as the source path, under it or above it, since the value would be lost by removing the source
+/// (e.g. in `.a.b => .a`, removing `.a.b` after the move would remove the `b` of the moved value instead).
+/// It is checked at compile time, comparing indices as written (e.g. `[i]` and `[0]` are considered different):
+///
+/// ```compile_fail
+/// # use serde_json::json;
+/// # use valq::migrate_value;
+/// let mut doc = json!({"a": {"b": 1}});
+/// migrate_value!(mut doc; .a => .a.c);
+/// ```
+///
+/// ```compile_fail
+/// # use serde_json::json;
+/// # use valq::migrate_value;
+/// let mut doc = json!({"a": {"b": 1}});
+/// migrate_value!(mut doc; .a.b => ."a".b);
+/// ```
+///
+/// ```compile_fail
+/// # use serde_json::json;
+/// # use valq::migrate_value;
+/// let mut doc = json!({"a": {"b": {"b": 1, "c": 2}}});
+/// migrate_value!(mut doc; .a.b => .a);
+/// ```
+#[macro_export]
+macro_rules! migrate_value {
+    /* parsing source path of a rule, keeping the last segment apart for removal */
+    (@src [$v:tt $($rule:tt)*] [$($parent:tt)*] [$($last:tt)+] => $($rest:tt)+) => {
+        $crate::migrate_value!(@dst [$v $($rule)*] [[$($parent)*] [$($last)+]] [] $($rest)+)
+    };
+    (@src [$v:tt $($rule:tt)*] [$($parent:tt)*] [$($last:tt)*] . $key:tt $($rest:tt)*) => {
+        $crate::migrate_value!(@src [$v $($rule)*] [$($parent)* $($last)*] [. $key] $($rest)*)
+    };
+    (@src [$v:tt $($rule:tt)*] [$($parent:tt)*] [$($last:tt)*] [$($idx:tt)+] $($rest:tt)*) => {
+        $crate::migrate_value!(@src [$v $($rule)*] [$($parent)* $($last)*] [[$($idx)+]] $($rest)*)
+    };
+    (@src [$v:tt $($rule:tt)*] [] []) => {
+        [$($crate::migrate_value!(@rule $v $rule)),*]
+    };
+    (@src $($_:tt)*) => {
+        compile_error!("invalid syntax for migrate_value!(): expected `migrate_value!(<value>; <src path> => <dst path> (with <fn>)?, ...)`")
+    };
+
+    /* parsing destination path (and transform) of a rule */
+    // `.` and the key are taken at once, so that a key named `with` is not confused with the keyword
+    (@dst [$v:tt $($rule:tt)*] $src:tt [$($dst:tt)+] , $($rest:tt)*) => {
+        $crate::migrate_value!(@src [$v $($rule)* ($src [$($dst)+] [])] [] [] $($rest)*)
+    };
+    (@dst [$v:tt $($rule:tt)*] $src:tt [$($dst:tt)+] with $f:expr $(, $($rest:tt)*)?) => {
+        $crate::migrate_value!(@src [$v $($rule)* ($src [$($dst)+] [$f])] [] [] $($($rest)*)?)
+    };
+    (@dst [$v:tt $($rule:tt)*] $src:tt [$($dst:tt)*] . $key:tt $($rest:tt)*) => {
+        $crate::migrate_value!(@dst [$v $($rule)*] $src [$($dst)* . $key] $($rest)*)
+    };
+    (@dst [$v:tt $($rule:tt)*] $src:tt [$($dst:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::migrate_value!(@dst [$v $($rule)*] $src [$($dst)* $t] $($rest)*)
+    };
+    (@dst [$v:tt $($rule:tt)*] $src:tt [$($dst:tt)+]) => {
+        $crate::migrate_value!(@src [$v $($rule)* ($src [$($dst)+] [])] [] [])
+    };
+
+    /* segments of a path as strings, for checking overlaps of paths at compile time */
+    (@seg_strs [$($s:expr),*]) => {
+        [$($s),*]
+    };
+    (@seg_strs [$($s:expr),*] . $key:ident $($rest:tt)*) => {
+        $crate::migrate_value!(@seg_strs [$($s,)* concat!(".", stringify!($key))] $($rest)*)
+    };
+    (@seg_strs [$($s:expr),*] . $key:literal $($rest:tt)*) => {
+        $crate::migrate_value!(@seg_strs [$($s,)* concat!(".", $key)] $($rest)*)
+    };
+    (@seg_strs [$($s:expr),*] [$($idx:tt)+] $($rest:tt)*) => {
+        $crate::migrate_value!(@seg_strs [$($s,)* concat!("[", stringify!($($idx)+), "]")] $($rest)*)
+    };
+    (@seg_strs [$($s:expr),*] $t:tt $($rest:tt)*) => {
+        $crate::migrate_value!(@seg_strs [$($s,)* stringify!($t)] $($rest)*)
+    };
+
+    /* applying a rule */
+    (@rule $v:tt ([[$($parent:tt)*] [$($last:tt)+]] [$($dst:tt)+] [$($f:expr)?])) => {
+        'rule: {
+            const _: () = {
+                let src = $crate::migrate_value!(@seg_strs [] $($parent)* $($last)+);
+                let dst = $crate::migrate_value!(@seg_strs [] $($dst)+);
+                assert!(
+                    !$crate::__private::path_starts_with(&dst, &src) && !$crate::__private::path_starts_with(&src, &dst),
+                    "destination path of migrate_value!() must not be the same as, under or above the source path",
+                );
+            };
+            let Some(val) = $crate::query_value!($v $($parent)* $($last)+).cloned() else { break 'rule false };
+            $(let val = ($f)(val);)?
+            if $crate::ensure_path!(@set $v = val; $($dst)+).is_none() {
+                break 'rule false;
+            }
+            $crate::delete_value!(@remove $v [$($parent)*] $($last)+);
+            true
+        }
+    };
+    /* entry point */
+    (mut $v:tt; $($rules:tt)+) => {
+        $crate::migrate_value!(@src [$v] [] [] $($rules)+)
+    };
+    ($v:tt; $($rules:tt)+) => {
+        $crate::migrate_value!(@src [$v] [] [] $($rules)+)
+    };
+}
+
+/// A macro for inserting a value into an array at a position.
+///
+/// Takes a path in the same syntax as [`query_value`](crate::query_value) (except for `-> <to_type>`; `mut` can be omitted) whose last segment is `[<idx>]`, `[-<idx>]` or `[<key> = <expr>]`, followed by `,` and the value to insert.
+/// The value is inserted at `<idx>` of the array, shifting all elements after it to the right (like `Vec::insert`).
+/// A negative index `[-<idx>]` counts from the end, so `[-1]` inserts the value before the last element.
+///
+/// Results in the mutable reference to the inserted value, or `None` if the array doesn't exist or `<idx>` is greater than the length of the array.
+///
+/// Available for data types that implement [`Editable`](crate::Editable).
+///
+/// ```
+/// use serde_json::json;
+/// use valq::insert_at;
+///
+/// let mut obj = json!({"steps": ["build", "deploy"]});
+///
+/// insert_at!(mut obj.steps[1], json!("test"));
+/// assert_eq!(obj["steps"], json!(["build", "test", "deploy"]));
+///
+/// // index out of range
+/// assert!(insert_at!(obj.steps[5], json!("notify")).is_none());
+/// ```
+#[macro_export]
+macro_rules! insert_at {
+    (@parent $v:tt) => {{
+        use $crate::__private::ReborrowMut as _;
+        Some($v.reborrow_mut())
+    }};
+    (@parent $($query:tt)+) => {
+        $crate::query_value!(mut $($query)+)
+    };
+
+    // index of the segment in the array `$elems`, also used by other macros
+    (@index $elems:ident [- $($n:tt)+]) => {
+        $crate::__private::resolve_index($elems.len(), (- $($n)+) as i64)
+    };
+    (@index $elems:ident [? $($pred:tt)*]) => {
+        compile_error!("`[? ...]` can't be the last segment of the path of insert_at!()")
+    };
+    (@index $elems:ident [*]) => {
+        compile_error!("`[*]` can't be the last segment of the path, since it selects multiple values")
+    };
+    (@index $elems:ident [.. $($hi:tt)*]) => {
+        compile_error!("slice segment `[<range>]` can't be the last segment of the path")
+    };
+    (@index $elems:ident [$lo:tt .. $($hi:tt)*]) => {
+        compile_error!("slice segment `[<range>]` can't be the last segment of the path")
+    };
+    (@index $elems:ident [..= $($hi:tt)*]) => {
+        compile_error!("slice segment `[<range>]` can't be the last segment of the path")
+    };
+    (@index $elems:ident [$lo:tt ..= $($hi:tt)*]) => {
+        compile_error!("slice segment `[<range>]` can't be the last segment of the path")
+    };
+    (@index $elems:ident [$field:ident = $val:expr]) => {
+        $crate::insert_at!(@index $elems [stringify!($field) => $val])
+    };
+    (@index $elems:ident [$field:literal = $val:expr]) => {
+        $crate::insert_at!(@index $elems [$field => $val])
+    };
+    (@index $elems:ident [$field:expr => $val:expr]) => {{
+        let want = &$val;
+        $elems.iter().position(|e| e.get($field as &str).is_some_and(|f| *f == *want))
+    }};
+    (@index $elems:ident [$idx:expr]) => {
+        Some($idx as usize)
+    };
+
+    (@split [$($parent:tt)+] [$($idx:tt)+] , $val:expr $(,)?) => {
+        match $crate::insert_at!(@parent $($parent)+).and_then($crate::Editable::elements_mut) {
+            Some(elems) => match $crate::insert_at!(@index elems [$($idx)+]) {
+                Some(i) if i <= elems.len() => {
+                    elems.insert(i, $val);
+                    elems.get_mut(i)
+                }
+                _ => None,
+            },
+            None => None,
+        }
+    };
+    (@split [$($parent:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::insert_at!(@split [$($parent)* $t] $($rest)*)
+    };
+    (@split $($_:tt)*) => {
+        compile_error!("invalid syntax for insert_at!(): expected `insert_at!(<query>[<idx>], <value>)`")
+    };
+
+    /* entry point */
+    (mut $($args:tt)+) => {
+        $crate::insert_at!(@split [] $($args)+)
+    };
+    ($($args:tt)+) => {
+        $crate::insert_at!(@split [] $($args)+)
+    };
+}
+
+/// A macro for removing the last element of an array and returning it.
+///
+/// Takes a query in the same syntax as [`query_value`](crate::query_value) (except for `-> <to_type>`; `mut` can be omitted) that points to the array.
+/// Results in the removed element, or `None` if the array doesn't exist or is empty.
+///
+/// Available for data types that implement [`Editable`](crate::Editable).
+///
+/// ```
+/// use serde_json::json;
+/// use valq::pop_value;
+///
+/// let mut obj = json!({"queue": ["a", "b"]});
+///
+/// assert_eq!(pop_value!(mut obj.queue), Some(json!("b")));
+/// assert_eq!(pop_value!(mut obj.queue), Some(json!("a")));
+/// assert_eq!(pop_value!(mut obj.queue), None);
+/// ```
+#[macro_export]
+macro_rules! pop_value {
+    (mut $($query:tt)+) => {
+        $crate::pop_value!($($query)+)
+    };
+    ($($query:tt)+) => {
+        $crate::query_value!(mut $($query)+)
+            .and_then($crate::Editable::elements_mut)
+            .and_then(Vec::pop)
+    };
+}
+
+/// A macro for removing an entry of an object or an element of an array at a path, and returning the removed value.
+///
+/// Takes a path in the same syntax as [`query_value`](crate::query_value) (except for `-> <to_type>`; `mut` can be omitted), whose last segment is `.<key>`, `[<idx>]`, `[-<idx>]`, `[<key> = <expr>]` or `[? <query> == <expr>]`.
+/// Removing an element of an array shifts all elements after it to the left (like `Vec::remove`). A negative index `[-<idx>]` counts from the end.
+/// Results in the removed value, or `None` if nothing is at the path.
+///
+/// Available for data types that implement [`Editable`](crate::Editable).
+///
+/// ```
+/// use serde_json::json;
+/// use valq::delete_value;
+///
+/// let mut obj = json!({"a": {"b": 1, "c": 2}, "arr": ["x", "y", "z"]});
+/// let i = 1;
+///
+/// assert_eq!(delete_value!(mut obj.a.b), Some(json!(1)));
+/// assert_eq!(delete_value!(mut obj.arr[i]), Some(json!("y")));
+/// assert_eq!(delete_value!(mut obj.a.unknown), None);
+/// assert_eq!(obj, json!({"a": {"c": 2}, "arr": ["x", "z"]}));
+/// ```
+#[macro_export]
+macro_rules! delete_value {
+    /* splitting the last segment of the path */
+    (@split $v:tt [$($parent:tt)*] [$($last:tt)*] . $key:tt $($rest:tt)*) => {
+        $crate::delete_value!(@split $v [$($parent)* $($last)*] [. $key] $($rest)*)
+    };
+    (@split $v:tt [$($parent:tt)*] [$($last:tt)*] [$($idx:tt)+] $($rest:tt)*) => {
+        $crate::delete_value!(@split $v [$($parent)* $($last)*] [[$($idx)+]] $($rest)*)
+    };
+    (@split $v:tt [$($parent:tt)*] [$($last:tt)+]) => {
+        $crate::delete_value!(@remove $v [$($parent)*] $($last)+)
+    };
+    (@split $($_:tt)*) => {
+        compile_error!("invalid syntax for delete_value!(): expected `delete_value!(<value>(.<key> | [<idx>])+)`")
+    };
+
+    /* removing the last segment from its parent, also used by other macros */
+    (@parent $v:tt) => {{
+        use $crate::__private::ReborrowMut as _;
+        Some($v.reborrow_mut())
+    }};
+    (@parent $v:tt $($query:tt)+) => {
+        $crate::query_value!(mut $v $($query)+)
+    };
+    (@remove $v:tt [$($parent:tt)*] . $key:ident) => {
+        $crate::delete_value!(@remove $v [$($parent)*] . stringify!($key))
+    };
+    (@remove $v:tt [$($parent:tt)*] . $key:expr) => {
+        match $crate::delete_value!(@parent $v $($parent)*) {
+            Some(p) => $crate::Editable::remove_entry(p, $key),
+            None => None,
+        }
+    };
+    (@remove $v:tt [$($parent:tt)*] [? $($pred:tt)+]) => {
+        match $crate::delete_value!(@parent $v $($parent)*) {
+            Some(p) => match $crate::query_value!(@pred_pos p [] [$($pred)+]) {
+                Some($crate::__private::ChildPos::Key(k)) => $crate::Editable::remove_entry(p, &k),
+                Some($crate::__private::ChildPos::Index(i)) => $crate::Editable::elements_mut(p).map(|elems| elems.remove(i)),
+                None => None,
+            },
+            None => None,
+        }
+    };
+    (@remove $v:tt [$($parent:tt)*] [$($idx:tt)+]) => {
+        match $crate::delete_value!(@parent $v $($parent)*).and_then($crate::Editable::elements_mut) {
+            Some(elems) => match $crate::insert_at!(@index elems [$($idx)+]) {
+                Some(i) if i < elems.len() => Some(elems.remove(i)),
+                _ => None,
+            },
+            None => None,
+        }
+    };
+
+    /* entry point */
+    (mut $v:tt $($path:tt)+) => {
+        $crate::delete_value!(@split $v [] [] $($path)+)
+    };
+    ($v:tt $($path:tt)+) => {
+        $crate::delete_value!(@split $v [] [] $($path)+)
+    };
+}
+
+/// A macro for generating a minimal sample value that satisfies the given queries, e.g. as a fixture for tests.
+///
+/// Takes the type of the value followed by `;`, and a comma-separated list of paths (in the same syntax as paths of [`ensure_path`](crate::ensure_path)) optionally followed by `-> <to_type>`.
+/// Each path is created with a sample value of the type at its end: `""` for `str`, `0` for `u64`/`i64`/`integer`, `0.0` for `f64`/`float`, `false` for `bool`, an empty array for `array`/`sequence`, and an empty object for `object`/`mapping`/`table` or paths without conversion.
+///
+/// Panics if a path conflicts with preceding ones (e.g. `.a -> str, .a.b -> str`).
+///
+/// Available for data types that implement [`Editable`](crate::Editable) and `From` for the sample values of the conversions used.
+///
+/// ```
+/// use serde_json::{json, Value};
+/// use valq::fixture_value;
+///
+/// let fixture = fixture_value!(Value; .user.name -> str, .user.age -> u64, .tags[0] -> str, .meta);
+/// assert_eq!(fixture, json!({"user": {"name": "", "age": 0}, "tags": [""], "meta": {}}));
+/// ```
+#[macro_export]
+macro_rules! fixture_value {
+    /* sample values */
+    (@sample str) => {
+        ::core::convert::From::from("")
+    };
+    (@sample u64) => {
+        ::core::convert::From::from(0u64)
+    };
+    (@sample i64) => {
+        ::core::convert::From::from(0i64)
+    };
+    (@sample integer) => {
+        ::core::convert::From::from(0i64)
+    };
+    (@sample f64) => {
+        ::core::convert::From::from(0.0f64)
+    };
+    (@sample float) => {
+        ::core::convert::From::from(0.0f64)
+    };
+    (@sample bool) => {
+        ::core::convert::From::from(false)
+    };
+    (@sample array) => {
+        $crate::Editable::new_array()
+    };
+    (@sample sequence) => {
+        $crate::Editable::new_array()
+    };
+    (@sample object) => {
+        $crate::Editable::new_object()
+    };
+    (@sample mapping) => {
+        $crate::Editable::new_object()
+    };
+    (@sample table) => {
+        $crate::Editable::new_object()
+    };
+    (@sample $to:ident) => {
+        compile_error!(concat!("unsupported target type `", stringify!($to), "` is specified in fixture_value!()"))
+    };
+
+    /* adding paths one by one */
+    (@add $root:ident [$($path:tt)+] $val:expr) => {
+        if $crate::ensure_path!(@set $root = $val; $($path)+).is_none() {
+            panic!(concat!("fixture_value!(): path `", stringify!($($path)+), "` conflicts with preceding ones"));
+        }
+    };
+    (@split $root:ident []) => {};
+    (@split $root:ident [$($path:tt)+] -> $to:ident $(, $($rest:tt)*)?) => {
+        $crate::fixture_value!(@add $root [$($path)+] $crate::fixture_value!(@sample $to));
+        $crate::fixture_value!(@split $root [] $($($rest)*)?);
+    };
+    (@split $root:ident [$($path:tt)+] $(, $($rest:tt)*)?) => {
+        $crate::fixture_value!(@add $root [$($path)+] $crate::Editable::new_object());
+        $crate::fixture_value!(@split $root [] $($($rest)*)?);
+    };
+    (@split $root:ident [$($path:tt)*] $t:tt $($rest:tt)*) => {
+        $crate::fixture_value!(@split $root [$($path)* $t] $($rest)*)
+    };
+
+    /* entry point */
+    ($t:ty; $($paths:tt)+) => {{
+        let mut root: $t = $crate::Editable::new_object();
+        $crate::fixture_value!(@split root [] $($paths)+);
+        root
+    }};
+}
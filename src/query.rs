@@ -0,0 +1,260 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Queryable;
+
+/// A query parsed at runtime, for when paths come from config files or user input rather than source code.
+///
+/// The syntax is the same as paths of [`query_value`](crate::query_value) without the root value, limited to keys and indices:
+/// `.key` (the leading `.` can be omitted for the first key), `."quoted key"` (with `\"` and `\\` escapes) and `[<idx>]` (an integer literal, counting from the end if negative: `[-1]` for the last element).
+/// The empty string is the query for the root itself.
+///
+/// Parse once, then evaluate against any value type implementing [`Queryable`] as many times as needed.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::Query;
+///
+/// let query = Query::parse("users[1].name").unwrap();
+///
+/// let mut obj = json!({"users": [{"name": "alice"}, {"name": "bob"}]});
+/// assert_eq!(query.get(&obj), Some(&json!("bob")));
+///
+/// *query.get_mut(&mut obj).unwrap() = json!("carol");
+/// assert_eq!(obj["users"][1]["name"], json!("carol"));
+///
+/// assert!(Query::parse("users[x]").is_err());
+/// ```
+///
+/// Queries can also be built up segment by segment, e.g. from fields picked in a UI.
+/// Conversions and fallbacks are done on the result with [`Queryable`] methods and `Option` combinators:
+///
+/// ```
+/// use serde_json::json;
+/// use valq::{Query, Queryable};
+///
+/// let query = Query::new().key("users").index(0).key("name");
+/// assert_eq!(query.to_string(), ".users[0].name");
+///
+/// let obj = json!({"users": [{"name": "alice"}]});
+/// let name = query.get(&obj).and_then(Queryable::as_str).unwrap_or("anonymous");
+/// assert_eq!(name, "alice");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Segment {
+    Key(String),
+    // negative indices count from the end, as in `query_value!()`
+    Index(i64),
+}
+
+impl Query {
+    /// Creates an empty query, which is the query for the root itself.
+    ///
+    /// Append segments with [`key`](Self::key), [`index`](Self::index) and [`index_from_end`](Self::index_from_end).
+    /// The builder has no counterpart of `-> <to_type>`: convert the result of [`get`](Self::get) with [`Queryable`] methods (e.g. [`Queryable::as_str`]) and fall back with `Option` combinators (e.g. `unwrap_or`).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a key segment to the query.
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.segments.push(Segment::Key(key.into()));
+        self
+    }
+
+    /// Appends an index segment to the query.
+    pub fn index(mut self, idx: usize) -> Self {
+        // indices beyond `i64::MAX` can't be in range anyway
+        self.segments
+            .push(Segment::Index(i64::try_from(idx).unwrap_or(i64::MAX)));
+        self
+    }
+
+    /// Appends an index segment counting from the end of an array to the query (e.g. `index_from_end(1)` for the last element, same as `[-1]`).
+    pub fn index_from_end(mut self, n: usize) -> Self {
+        self.segments
+            .push(Segment::Index(i64::try_from(n).map_or(i64::MIN, |n| -n)));
+        self
+    }
+
+    /// Parses a query string.
+    pub fn parse(s: &str) -> Result<Self, QueryParseError> {
+        Parser { s, pos: 0 }.parse()
+    }
+
+    /// Evaluates the query against the value, returning the value at the end of the query.
+    pub fn get<'a, V: Queryable>(&self, v: &'a V) -> Option<&'a V> {
+        self.segments.iter().try_fold(v, |cur, seg| match seg {
+            Segment::Key(k) => cur.get_by_key(k),
+            Segment::Index(i) => cur.get_by_index(resolve_index(cur, *i)?),
+        })
+    }
+
+    /// Evaluates the query against the value, returning the mutable reference to the value at the end of the query.
+    pub fn get_mut<'a, V: Queryable>(&self, v: &'a mut V) -> Option<&'a mut V> {
+        self.segments.iter().try_fold(v, |cur, seg| match seg {
+            Segment::Key(k) => cur.get_by_key_mut(k),
+            Segment::Index(i) => cur.get_by_index_mut(resolve_index(cur, *i)?),
+        })
+    }
+}
+
+fn resolve_index<V: Queryable>(v: &V, idx: i64) -> Option<usize> {
+    if idx < 0 {
+        crate::__private::resolve_index(v.array_len()?, idx)
+    } else {
+        usize::try_from(idx).ok()
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// Formats the query in the same format as paths from [`query_value_with_path`](crate::query_value_with_path) (e.g. `.users[1].name`), which can be parsed back.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for seg in &self.segments {
+            match seg {
+                Segment::Key(k) => write_key(f, k)?,
+                Segment::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the key segment `.key`, quoting the key as `."key"` if it isn't an identifier.
+pub(crate) fn write_key<W: fmt::Write>(w: &mut W, k: &str) -> fmt::Result {
+    if is_plain_key(k) {
+        return write!(w, ".{}", k);
+    }
+    w.write_str(".\"")?;
+    for c in k.chars() {
+        if c == '"' || c == '\\' {
+            w.write_str("\\")?;
+        }
+        w.write_char(c)?;
+    }
+    w.write_str("\"")
+}
+
+fn is_plain_key(k: &str) -> bool {
+    k.chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && k.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// An error on parsing a [`Query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryParseError {
+    pos: usize,
+    msg: &'static str,
+}
+
+impl QueryParseError {
+    /// Returns the byte position in the query string where the error occurred.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+impl fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid query at {}: {}", self.pos, self.msg)
+    }
+}
+
+impl std::error::Error for QueryParseError {}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn parse(mut self) -> Result<Query, QueryParseError> {
+        let mut segments = Vec::new();
+        // the leading `.` can be omitted for the first key
+        if !self.s.is_empty() && !self.s.starts_with(['.', '[']) {
+            segments.push(Segment::Key(self.key()?));
+        }
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            let seg = match c {
+                '.' => Segment::Key(self.key()?),
+                '[' => Segment::Index(self.index()?),
+                _ => return Err(self.err_at(self.pos - 1, "expected `.` or `[`")),
+            };
+            segments.push(seg);
+        }
+        Ok(Query { segments })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn err_at(&self, pos: usize, msg: &'static str) -> QueryParseError {
+        QueryParseError { pos, msg }
+    }
+
+    fn key(&mut self) -> Result<String, QueryParseError> {
+        if self.peek() == Some('"') {
+            return self.quoted_key();
+        }
+        let rest = &self.s[self.pos..];
+        let len = rest.find(['.', '[', ']', '"']).unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.err_at(self.pos, "expected a key"));
+        }
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    fn quoted_key(&mut self) -> Result<String, QueryParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut key = String::new();
+        let mut chars = self.s[self.pos..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(key);
+                }
+                '\\' => match chars.next() {
+                    Some((_, c @ ('"' | '\\'))) => key.push(c),
+                    _ => return Err(self.err_at(self.pos + i, "invalid escape in quoted key")),
+                },
+                c => key.push(c),
+            }
+        }
+        Err(self.err_at(start, "unterminated quoted key"))
+    }
+
+    fn index(&mut self) -> Result<i64, QueryParseError> {
+        let rest = &self.s[self.pos..];
+        let Some(len) = rest.find(']') else {
+            return Err(self.err_at(self.pos - 1, "unterminated index"));
+        };
+        // only digits with an optional `-`, since `i64::from_str` accepts a leading `+` as well
+        let digits = rest[..len].strip_prefix('-').unwrap_or(&rest[..len]);
+        let i = Some(&rest[..len])
+            .filter(|_| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| self.err_at(self.pos, "index must be an integer"))?;
+        self.pos += len + 1;
+        Ok(i)
+    }
+}
@@ -0,0 +1,172 @@
+/// A trait formally defining structured data types that can be queried at runtime by [`Query`](crate::Query).
+///
+/// The macros such as [`query_value`](crate::query_value) don't require this trait: they work with any type that has `get`/`get_mut`/`as_xxx` methods of the expected shape (see "Compatibility" section of `query_value`).
+/// This trait defines the same capabilities (getting by key or index, and conversions to primitive types) as trait methods, so that generic code such as the runtime query API can use them,
+/// and third-party value types can plug in explicitly by implementing it.
+///
+/// Implementations for the following data types are provided, each behind a cargo feature of the same name as the crate:
+///
+/// - `serde_json::Value` (feature: `serde_json`)
+/// - `serde_yaml::Value` (feature: `serde_yaml`)
+/// - `toml::Value` (feature: `toml`)
+pub trait Queryable: Sized {
+    /// Returns the value of the entry with the key if the value is an object.
+    fn get_by_key(&self, key: &str) -> Option<&Self>;
+
+    /// Returns the mutable reference to the value of the entry with the key if the value is an object.
+    fn get_by_key_mut(&mut self, key: &str) -> Option<&mut Self>;
+
+    /// Returns the element at the index if the value is an array.
+    fn get_by_index(&self, idx: usize) -> Option<&Self>;
+
+    /// Returns the mutable reference to the element at the index if the value is an array.
+    fn get_by_index_mut(&mut self, idx: usize) -> Option<&mut Self>;
+
+    /// Returns the number of elements if the value is an array.
+    fn array_len(&self) -> Option<usize>;
+
+    /// Returns the string if the value is a string.
+    fn as_str(&self) -> Option<&str>;
+
+    /// Returns the boolean if the value is a boolean.
+    fn as_bool(&self) -> Option<bool>;
+
+    /// Returns the integer if the value is an integer that fits in `i64`.
+    fn as_i64(&self) -> Option<i64>;
+
+    /// Returns the integer if the value is an integer that fits in `u64`.
+    fn as_u64(&self) -> Option<u64>;
+
+    /// Returns the number as `f64` if the value is a number.
+    fn as_f64(&self) -> Option<f64>;
+}
+
+#[cfg(feature = "serde_json")]
+impl Queryable for serde_json::Value {
+    fn get_by_key(&self, key: &str) -> Option<&Self> {
+        self.get(key)
+    }
+
+    fn get_by_key_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.get_mut(key)
+    }
+
+    fn get_by_index(&self, idx: usize) -> Option<&Self> {
+        self.get(idx)
+    }
+
+    fn get_by_index_mut(&mut self, idx: usize) -> Option<&mut Self> {
+        self.get_mut(idx)
+    }
+
+    fn array_len(&self) -> Option<usize> {
+        self.as_array().map(Vec::len)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        self.as_str()
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        self.as_bool()
+    }
+
+    fn as_i64(&self) -> Option<i64> {
+        self.as_i64()
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        self.as_u64()
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        self.as_f64()
+    }
+}
+
+#[cfg(feature = "serde_yaml")]
+impl Queryable for serde_yaml::Value {
+    fn get_by_key(&self, key: &str) -> Option<&Self> {
+        self.get(key)
+    }
+
+    fn get_by_key_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.get_mut(key)
+    }
+
+    fn get_by_index(&self, idx: usize) -> Option<&Self> {
+        self.get(idx)
+    }
+
+    fn get_by_index_mut(&mut self, idx: usize) -> Option<&mut Self> {
+        self.get_mut(idx)
+    }
+
+    fn array_len(&self) -> Option<usize> {
+        self.as_sequence().map(Vec::len)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        self.as_str()
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        self.as_bool()
+    }
+
+    fn as_i64(&self) -> Option<i64> {
+        self.as_i64()
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        self.as_u64()
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        self.as_f64()
+    }
+}
+
+#[cfg(feature = "toml")]
+impl Queryable for toml::Value {
+    fn get_by_key(&self, key: &str) -> Option<&Self> {
+        self.get(key)
+    }
+
+    fn get_by_key_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.get_mut(key)
+    }
+
+    fn get_by_index(&self, idx: usize) -> Option<&Self> {
+        self.get(idx)
+    }
+
+    fn get_by_index_mut(&mut self, idx: usize) -> Option<&mut Self> {
+        self.get_mut(idx)
+    }
+
+    fn array_len(&self) -> Option<usize> {
+        self.as_array().map(Vec::len)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        self.as_str()
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        self.as_bool()
+    }
+
+    fn as_i64(&self) -> Option<i64> {
+        self.as_integer()
+    }
+
+    // TOML integers are `i64`
+    fn as_u64(&self) -> Option<u64> {
+        self.as_integer().and_then(|i| u64::try_from(i).ok())
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        self.as_float()
+    }
+}
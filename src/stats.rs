@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use crate::{Editable, Queryable};
+
+/// Shape metrics of a value, computed by [`value_stats`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValueStats {
+    /// Maximum nesting depth of objects and arrays. `0` for scalars, `1` for containers with only scalars (or nothing) in them.
+    pub depth: usize,
+    /// Number of values in the value, including itself.
+    pub node_count: usize,
+    /// Distribution of lengths of arrays in the value: for each length, the number of arrays with that length.
+    pub array_lens: BTreeMap<usize, usize>,
+    /// Approximate memory size of the value in bytes: the size of value nodes plus the lengths of object keys and strings.
+    /// Other overheads (e.g. spare capacity of containers) are not counted.
+    pub approx_size: usize,
+}
+
+impl ValueStats {
+    /// Returns the length of the longest array in the value, or `0` if there are no arrays.
+    pub fn max_array_len(&self) -> usize {
+        self.array_lens.keys().next_back().copied().unwrap_or(0)
+    }
+}
+
+/// Computes shape metrics of a value in a single traversal, e.g. for capacity planning or rejecting abusive payloads.
+/// Use [`stats_at`](crate::stats_at) to compute them for a value at a query.
+///
+/// Entries with non-string keys (possible in YAML) are not counted.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::value_stats;
+///
+/// let stats = value_stats(&json!({"items": [[1, 2], [3, 4], []], "name": "x"}));
+///
+/// assert_eq!(stats.depth, 3);
+/// assert_eq!(stats.node_count, 10);
+/// assert_eq!(stats.max_array_len(), 3);
+/// assert_eq!(stats.array_lens.get(&2), Some(&2));
+/// ```
+pub fn value_stats<V: Editable + Queryable>(v: &V) -> ValueStats {
+    let mut stats = ValueStats::default();
+    // traverses with an explicit stack rather than recursion, so that deeply nested values don't overflow the stack.
+    // each value is paired with the number of containers enclosing it
+    let mut stack = vec![(v, 0)];
+    while let Some((v, level)) = stack.pop() {
+        stats.node_count += 1;
+        stats.approx_size += std::mem::size_of::<V>();
+
+        if let Some(entries) = v.entries() {
+            stats.depth = stats.depth.max(level + 1);
+            for (k, v) in entries {
+                stats.approx_size += k.len();
+                stack.push((v, level + 1));
+            }
+        } else if let Some(elems) = v.elements() {
+            stats.depth = stats.depth.max(level + 1);
+            *stats.array_lens.entry(elems.len()).or_default() += 1;
+            stack.extend(elems.iter().map(|e| (e, level + 1)));
+        } else if let Some(s) = v.as_str() {
+            stats.approx_size += s.len();
+        }
+    }
+    stats
+}
+
+/// A macro for computing shape metrics of the value at a query by [`value_stats`](crate::value_stats).
+///
+/// Takes the same query syntax as [`query_value`](crate::query_value) (except for `mut`), and returns `None` if the query fails.
+///
+/// ```
+/// use serde_json::json;
+/// use valq::stats_at;
+///
+/// let req = json!({"payload": {"tags": ["a", "b"], "meta": {"nested": {}}}});
+///
+/// let stats = stats_at!(req.payload).unwrap();
+/// assert_eq!(stats.depth, 3);
+/// assert_eq!(stats.node_count, 6);
+/// assert!(stats_at!(req.unknown).is_none());
+/// ```
+#[macro_export]
+macro_rules! stats_at {
+    ($($query:tt)+) => {
+        $crate::query_value!($($query)+).map($crate::value_stats)
+    };
+}
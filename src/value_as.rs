@@ -0,0 +1,35 @@
+/// A trait for user-defined conversions of queried values.
+///
+/// If a target type of conversion query `-> <to_type>` is not one of the built-in ones (`str`, `u64`, `object`, ...), `query_value` tries to convert the queried value by `ValueAs<to_type>`.
+/// By implementing this trait for a value type, you can convert values to your own types in queries.
+///
+/// ```
+/// use serde_json::{json, Value};
+/// use valq::{query_value, ValueAs};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Money {
+///     amount: u64,
+///     currency: String,
+/// }
+///
+/// impl ValueAs<Money> for Value {
+///     fn value_as(&self) -> Option<Money> {
+///         let (amount, currency) = self.as_str()?.split_once(' ')?;
+///         Some(Money {
+///             amount: amount.parse().ok()?,
+///             currency: currency.to_string(),
+///         })
+///     }
+/// }
+///
+/// let obj = json!({"item": {"price": "100 JPY"}});
+/// assert_eq!(
+///     query_value!(obj.item.price -> Money),
+///     Some(Money { amount: 100, currency: "JPY".to_string() }),
+/// );
+/// ```
+pub trait ValueAs<T> {
+    /// Converts the value to `T`. Returns `None` if the value can't be converted.
+    fn value_as(&self) -> Option<T>;
+}